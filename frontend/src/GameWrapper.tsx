@@ -24,6 +24,15 @@ type TGameContext = {
 };
 export let GameContext: React.Context<TGameContext>;
 
+/** Errors thrown by the game are named `ChessError` and carry a stable `code`, e.g. "illegal_move". */
+export function isChessError(e: unknown, ...codes: string[]): boolean {
+  if (!(e instanceof Error) || e.name !== "ChessError") {
+    return false;
+  }
+  const code = (e as Error & { code?: string }).code;
+  return code !== undefined && codes.includes(code);
+}
+
 export default function GameWrapper({ children }: { children: React.ReactNode }) {
   const [game, setGame] = useState<TGame>();
   const [gameNum, setGameNum] = useState<number>(0);
@@ -1,6 +1,6 @@
 import Board from "./Board.js";
 import { useContext, useState } from "react";
-import { GameContext } from "./GameWrapper.js";
+import { GameContext, isChessError } from "./GameWrapper.js";
 export default function LocalGame() {
   const { game, UpdateGame } = useContext(GameContext);
   const [switchSides, setSwitchSides] = useState(false);
@@ -9,34 +9,30 @@ export default function LocalGame() {
 
   async function onPieceMove(start_sq_coords: string, end_sq_coords: string) {
     try {
-      MovePiece(start_sq_coords, end_sq_coords);
-      HandleLastPawn();
+      MovePiece(start_sq_coords, end_sq_coords, ChoosePromotion(start_sq_coords, end_sq_coords));
       game.is_white_view = game.is_white_turn();
       UpdateGame();
       HandleCheck();
-    } catch (e: any) {
-      if (!e.includes("Invalid Move")) {
+    } catch (e: unknown) {
+      if (!isChessError(e, "illegal_move", "invalid_promotion")) {
         console.error(e);
       }
       UpdateGame();
     }
   }
 
-  function MovePiece(start_sq_coords: string, end_sq_coords: string) {
-    game.lastMoved = game.move_piece(start_sq_coords, end_sq_coords);
+  function MovePiece(start_sq_coords: string, end_sq_coords: string, promotion?: string) {
+    game.lastMoved = game.move_piece(start_sq_coords, end_sq_coords, promotion);
     game.movedFrom = game.lastMoved.map((move) => move[0]);
     game.movedTo = game.lastMoved.map((move) => move[1]);
     UpdateGame();
   }
 
-  function HandleLastPawn() {
-    if (game.has_last_rank_pawn()) {
-      const promotionPiece = prompt("Promotion Piece: ") || "Queen";
-      game.replace_last_rank_pawn(promotionPiece);
-      UpdateGame();
-      return promotionPiece;
+  function ChoosePromotion(start_sq_coords: string, end_sq_coords: string) {
+    if (game.is_promotion(start_sq_coords, end_sq_coords)) {
+      return prompt("Promotion Piece: ") || "Queen";
     }
-    return "";
+    return undefined;
   }
 
   function HandleCheck() {
@@ -1,6 +1,6 @@
 import Board from "./Board.js";
 import { useContext, useEffect, useState } from "react";
-import { GameContext, TRuleSet } from "./GameWrapper.js";
+import { GameContext, TRuleSet, isChessError } from "./GameWrapper.js";
 import { backendHost } from "../constants.ts";
 import { useParams } from "react-router-dom";
 
@@ -35,6 +35,19 @@ type TInitMessage = {
   num: number;
 };
 
+type TSettingsMessage = {
+  message_type: "settings";
+  room_id: string;
+  settings: { rule_set: TRuleSet };
+};
+
+/** Sent by the server once it settles colours, e.g. when the opponent is its bot. */
+type TColourMessage = {
+  message_type: "colour";
+  colour: "White" | "Black";
+  white: string;
+};
+
 type TResetMessage = {
   message_type: "reset";
   game_data: any;
@@ -57,6 +70,8 @@ type TMessageData =
   | TJoinMessage
   | TErrorMessage
   | TResetMessage
+  | TSettingsMessage
+  | TColourMessage
   | TLeaveMessage;
 
 type TMessage = {
@@ -83,6 +98,10 @@ export default function WebSocketWrapper() {
 
     const wsUrl = `${proto}://${backendHost}/websocket/${room_id}/${id}/${rule_set}`;
     const webSocket: WebSocket = new WebSocket(wsUrl);
+    // colours the server settles win over the random numbers the clients swap
+    let coloursFromServer = false;
+    // moves that arrive before the board is shown, such as a bot's opening move as White
+    const pendingMoves: TMoveMessage[] = [];
 
     webSocket.onopen = () => {
       console.log("Connected");
@@ -107,6 +126,9 @@ export default function WebSocketWrapper() {
     }
 
     function SetTeams(num: number, opp_id: string) {
+      if (coloursFromServer) {
+        return;
+      }
       game.opp_random_number = num;
       if (num > game.my_random_number) {
         game.is_white_view = true;
@@ -121,6 +143,15 @@ export default function WebSocketWrapper() {
       UpdateGame();
     }
 
+    function SetColour(colour: "White" | "Black", white: string) {
+      coloursFromServer = true;
+      game.is_white_view = colour === "White";
+      game.white_id = white;
+      game.black_id = game.is_white_view ? "" : id;
+      setTeamsChosen(true);
+      UpdateGame();
+    }
+
     function OnReceiveMessage(callback: (data: TMoveMessage) => void) {
       webSocket.onmessage = (e) => {
         const message: TMessage = JSON.parse(e.data);
@@ -185,10 +216,18 @@ export default function WebSocketWrapper() {
             throw new Error("Rule set mismatch");
           }
           SetTeams(data.num, message.sender_id);
+        } else if (data.message_type === "settings") {
+          if (rule_set !== data.settings.rule_set) {
+            throw new Error("Rule set mismatch");
+          }
+        } else if (data.message_type === "colour") {
+          SetColour(data.colour, data.white);
         }
       };
+      // played outside the render that hands over the callback
+      setTimeout(() => pendingMoves.splice(0).forEach(callback));
     }
-    OnReceiveMessage(() => {});
+    OnReceiveMessage((data) => pendingMoves.push(data));
 
     function UpdateGame() {
       setCount((prev) => prev + 1);
@@ -238,33 +277,30 @@ function OnlineGame({
 
   async function onPieceMove(start_sq_coords: string, end_sq_coords: string) {
     try {
-      MovePiece(start_sq_coords, end_sq_coords);
-      const lastPawnAction = HandleLastPawn();
-      SendMove(start_sq_coords, end_sq_coords, lastPawnAction);
+      const promotion = ChoosePromotion(start_sq_coords, end_sq_coords);
+      MovePiece(start_sq_coords, end_sq_coords, promotion);
+      SendMove(start_sq_coords, end_sq_coords, promotion || "");
       HandleCheck();
-    } catch (e: any) {
-      if (!e.includes("Invalid Move")) {
+    } catch (e: unknown) {
+      if (!isChessError(e, "illegal_move", "invalid_promotion")) {
         console.error(e);
       }
       UpdateGame();
     }
   }
 
-  function MovePiece(start_sq_coords: string, end_sq_coords: string) {
-    game.lastMoved = game.move_piece(start_sq_coords, end_sq_coords);
+  function MovePiece(start_sq_coords: string, end_sq_coords: string, promotion?: string) {
+    game.lastMoved = game.move_piece(start_sq_coords, end_sq_coords, promotion);
     game.movedFrom = game.lastMoved.map((move) => move[0]);
     game.movedTo = game.lastMoved.map((move) => move[1]);
     UpdateGame();
   }
 
-  function HandleLastPawn() {
-    if (game.has_last_rank_pawn()) {
-      const promotionPiece = prompt("Promotion Piece: ") || "Queen";
-      game.replace_last_rank_pawn(promotionPiece);
-      UpdateGame();
-      return promotionPiece;
+  function ChoosePromotion(start_sq_coords: string, end_sq_coords: string) {
+    if (game.is_promotion(start_sq_coords, end_sq_coords)) {
+      return prompt("Promotion Piece: ") || "Queen";
     }
-    return "";
+    return undefined;
   }
 
   function SendMove(start_sq_coords: string, end_sq_coords: string, lastPawnAction: string) {
@@ -279,11 +315,7 @@ function OnlineGame({
 
   OnReceiveMessage((data) => {
     const { start_sq_coords, end_sq_coords, lastPawnAction } = data;
-    MovePiece(start_sq_coords, end_sq_coords);
-    if (lastPawnAction) {
-      game.replace_last_rank_pawn(lastPawnAction);
-      UpdateGame();
-    }
+    MovePiece(start_sq_coords, end_sq_coords, lastPawnAction || undefined);
     HandleCheck();
   });
 
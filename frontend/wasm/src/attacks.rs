@@ -0,0 +1,138 @@
+//! Attack tables, all built at compile time. Sliding pieces use the classical ray lookup: the ray
+//! in each direction is cut off behind its first blocker, which needs no magic numbers or PEXT.
+
+use crate::{bitboard::Bitboard, Team};
+
+/// Rank and file steps, ordered so the first four directions run towards h8 (rising square
+/// indices) and the last four towards a1.
+const DIRECTIONS: [(i32, i32); 8] = [(1, 0), (1, 1), (0, 1), (1, -1), (-1, 0), (-1, -1), (0, -1), (-1, 1)];
+const ROOK_DIRECTIONS: [usize; 4] = [0, 2, 4, 6];
+const BISHOP_DIRECTIONS: [usize; 4] = [1, 3, 5, 7];
+const KNIGHT_STEPS: [(i32, i32); 8] = [(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)];
+
+static KNIGHT: [u64; 64] = leaper_table(&KNIGHT_STEPS);
+static KING: [u64; 64] = leaper_table(&DIRECTIONS);
+static PAWN: [[u64; 64]; 2] = [leaper_table(&[(1, -1), (1, 1)]), leaper_table(&[(-1, -1), (-1, 1)])];
+static RAYS: [[u64; 64]; 8] = ray_table();
+static BETWEEN: [[u64; 64]; 64] = line_tables().0;
+static LINE: [[u64; 64]; 64] = line_tables().1;
+
+/// The square `steps` away from `sq`, if it's on the board.
+const fn offset(sq: usize, (rank_step, file_step): (i32, i32)) -> Option<usize> {
+    let rank = (sq / 8) as i32 + rank_step;
+    let file = (sq % 8) as i32 + file_step;
+    if rank < 0 || rank > 7 || file < 0 || file > 7 {
+        return None;
+    }
+    Some((rank * 8 + file) as usize)
+}
+
+const fn leaper_table(steps: &[(i32, i32)]) -> [u64; 64] {
+    let mut table = [0; 64];
+    let mut sq = 0;
+    while sq < 64 {
+        let mut i = 0;
+        while i < steps.len() {
+            if let Some(target) = offset(sq, steps[i]) {
+                table[sq] |= 1 << target;
+            }
+            i += 1;
+        }
+        sq += 1;
+    }
+    table
+}
+
+const fn ray_table() -> [[u64; 64]; 8] {
+    let mut table = [[0; 64]; 8];
+    let mut direction = 0;
+    while direction < 8 {
+        let mut sq = 0;
+        while sq < 64 {
+            let mut current = offset(sq, DIRECTIONS[direction]);
+            while let Some(target) = current {
+                table[direction][sq] |= 1 << target;
+                current = offset(target, DIRECTIONS[direction]);
+            }
+            sq += 1;
+        }
+        direction += 1;
+    }
+    table
+}
+
+/// Squares strictly between two squares on a shared line, and the whole line through them.
+const fn line_tables() -> ([[u64; 64]; 64], [[u64; 64]; 64]) {
+    let rays = ray_table();
+    let mut between = [[0; 64]; 64];
+    let mut line = [[0; 64]; 64];
+    let mut sq = 0;
+    while sq < 64 {
+        let mut direction = 0;
+        while direction < 8 {
+            let full_line = rays[direction][sq] | rays[(direction + 4) % 8][sq] | 1 << sq;
+            let mut passed = 0;
+            let mut current = offset(sq, DIRECTIONS[direction]);
+            while let Some(target) = current {
+                between[sq][target] = passed;
+                line[sq][target] = full_line;
+                passed |= 1 << target;
+                current = offset(target, DIRECTIONS[direction]);
+            }
+            direction += 1;
+        }
+        sq += 1;
+    }
+    (between, line)
+}
+
+fn ray(sq: usize, direction: usize, occupied: Bitboard) -> Bitboard {
+    let ray = RAYS[direction][sq];
+    let blockers = ray & occupied.0;
+    if blockers == 0 {
+        return Bitboard(ray);
+    }
+    let blocker = if direction < 4 {
+        blockers.trailing_zeros()
+    } else {
+        63 - blockers.leading_zeros()
+    };
+    Bitboard(ray ^ RAYS[direction][blocker as usize])
+}
+
+fn slide(sq: usize, directions: [usize; 4], occupied: Bitboard) -> Bitboard {
+    directions.into_iter().fold(Bitboard::EMPTY, |attacks, direction| {
+        attacks | ray(sq, direction, occupied)
+    })
+}
+
+pub(crate) fn knight(sq: usize) -> Bitboard {
+    Bitboard(KNIGHT[sq])
+}
+
+pub(crate) fn king(sq: usize) -> Bitboard {
+    Bitboard(KING[sq])
+}
+
+/// Squares a `team` pawn on `sq` captures on.
+pub(crate) fn pawn(team: Team, sq: usize) -> Bitboard {
+    Bitboard(PAWN[team as usize][sq])
+}
+
+pub(crate) fn bishop(sq: usize, occupied: Bitboard) -> Bitboard {
+    slide(sq, BISHOP_DIRECTIONS, occupied)
+}
+
+pub(crate) fn rook(sq: usize, occupied: Bitboard) -> Bitboard {
+    slide(sq, ROOK_DIRECTIONS, occupied)
+}
+
+/// Empty unless the squares share a rank, file or diagonal.
+pub(crate) fn between(from: usize, to: usize) -> Bitboard {
+    Bitboard(BETWEEN[from][to])
+}
+
+/// The whole rank, file or diagonal through both squares, or empty if they share none.
+pub(crate) fn line(from: usize, to: usize) -> Bitboard {
+    Bitboard(LINE[from][to])
+}
@@ -0,0 +1,71 @@
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not};
+
+/// A set of squares, one bit each, with a1 as bit 0, b1 as bit 1 and h8 as bit 63.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) struct Bitboard(pub(crate) u64);
+
+impl Bitboard {
+    pub(crate) const EMPTY: Bitboard = Bitboard(0);
+    pub(crate) const ALL: Bitboard = Bitboard(!0);
+
+    pub(crate) const fn square(sq: usize) -> Bitboard {
+        Bitboard(1 << sq)
+    }
+    pub(crate) const fn rank(rank: usize) -> Bitboard {
+        Bitboard(0xff << (rank * 8))
+    }
+    pub(crate) fn contains(self, sq: usize) -> bool {
+        self.0 & (1 << sq) != 0
+    }
+    pub(crate) fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+    pub(crate) fn more_than_one(self) -> bool {
+        self.0 & self.0.wrapping_sub(1) != 0
+    }
+    /// The lowest square in the set.
+    pub(crate) fn first(self) -> Option<usize> {
+        (!self.is_empty()).then(|| self.0.trailing_zeros() as usize)
+    }
+}
+
+/// Yields squares from a1 towards h8, emptying the set as it goes.
+impl Iterator for Bitboard {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        let sq = self.first()?;
+        self.0 &= self.0 - 1;
+        Some(sq)
+    }
+}
+
+macro_rules! bit_op {
+    ($op:ident, $fn:ident, $assign_op:ident, $assign_fn:ident) => {
+        impl $op for Bitboard {
+            type Output = Bitboard;
+
+            fn $fn(self, rhs: Bitboard) -> Bitboard {
+                Bitboard($op::$fn(self.0, rhs.0))
+            }
+        }
+
+        impl $assign_op for Bitboard {
+            fn $assign_fn(&mut self, rhs: Bitboard) {
+                $assign_op::$assign_fn(&mut self.0, rhs.0)
+            }
+        }
+    };
+}
+
+bit_op!(BitAnd, bitand, BitAndAssign, bitand_assign);
+bit_op!(BitOr, bitor, BitOrAssign, bitor_assign);
+bit_op!(BitXor, bitxor, BitXorAssign, bitxor_assign);
+
+impl Not for Bitboard {
+    type Output = Bitboard;
+
+    fn not(self) -> Bitboard {
+        Bitboard(!self.0)
+    }
+}
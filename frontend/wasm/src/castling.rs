@@ -0,0 +1,52 @@
+//! Castling rights, kept as the squares of the rooks each side may still castle with. A right is
+//! revoked when its king moves or when anything leaves or lands on its rook's square, so a
+//! captured rook can't be castled with even if another rook later takes its place.
+
+use serde::{Deserialize, Serialize};
+
+use crate::Team;
+
+/// Rook squares indexed by team, then a-side (queenside) or h-side (kingside).
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub(crate) struct CastlingRights([[Option<usize>; 2]; 2]);
+
+impl CastlingRights {
+    pub(crate) fn rook(&self, team: Team, h_side: bool) -> Option<usize> {
+        self.0[team as usize][h_side as usize]
+    }
+    pub(crate) fn rooks(&self, team: Team) -> impl Iterator<Item = usize> {
+        self.0[team as usize].into_iter().flatten()
+    }
+    pub(crate) fn grant(&mut self, team: Team, h_side: bool, rook_sq: usize) {
+        self.0[team as usize][h_side as usize] = Some(rook_sq);
+    }
+    /// Both rights, once the king has moved.
+    pub(crate) fn revoke(&mut self, team: Team) {
+        self.0[team as usize] = [None; 2];
+    }
+    /// Whichever right belongs to a rook on `sq`, whether the rook moved away or was captured.
+    pub(crate) fn revoke_rook(&mut self, sq: usize) {
+        for rook in self.0.iter_mut().flatten() {
+            if *rook == Some(sq) {
+                *rook = None;
+            }
+        }
+    }
+}
+
+/// Whether the king and rook start from the standard squares, the e-file and a corner, so castling
+/// reads as the king moving two squares.
+pub(crate) fn castles_two_squares(king_sq: usize, rook_sq: usize) -> bool {
+    king_sq % 8 == 4 && matches!(rook_sq % 8, 0 | 7)
+}
+
+/// Where the king and rook land: the king on the c- or g-file and the rook beside it, towards the
+/// centre, wherever they started.
+pub(crate) fn castling_targets(king_sq: usize, rook_sq: usize) -> (usize, usize) {
+    let back_rank = king_sq / 8 * 8;
+    if rook_sq > king_sq {
+        (back_rank + 6, back_rank + 5)
+    } else {
+        (back_rank + 2, back_rank + 3)
+    }
+}
@@ -0,0 +1,112 @@
+//! Bot strength from 1 to 10. Weaker levels search shallower and for less time, pick at random
+//! among the moves scoring close to the best one, and now and then play any legal move at all.
+
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    engine::{now_ms, Search, SearchLimits, SearchResult},
+    to_js, ChessError, Game,
+    Team::*,
+};
+
+/// What a level is allowed to do.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Difficulty {
+    pub depth: u32,
+    pub time_ms: u64,
+    /// Moves up to this many centipawns worse than the best are as likely to be played.
+    pub margin: i32,
+    /// The chance of playing a random legal move instead of searching.
+    pub blunder_chance: f64,
+}
+
+impl Difficulty {
+    pub const LEVELS: std::ops::RangeInclusive<u8> = 1..=10;
+
+    pub fn level(level: u8) -> Result<Difficulty, ChessError> {
+        let (depth, time_ms, margin, blunder_chance) = match level {
+            1 => (1, 50, 300, 0.3),
+            2 => (1, 100, 200, 0.2),
+            3 => (2, 150, 150, 0.15),
+            4 => (2, 200, 100, 0.1),
+            5 => (3, 300, 75, 0.07),
+            6 => (3, 400, 50, 0.05),
+            7 => (4, 600, 30, 0.03),
+            8 => (5, 800, 15, 0.01),
+            9 => (6, 1200, 5, 0.0),
+            10 => (63, 2000, 0, 0.0),
+            _ => return Err(ChessError::InvalidLevel(level)),
+        };
+        Ok(Difficulty {
+            depth,
+            time_ms,
+            margin,
+            blunder_chance,
+        })
+    }
+}
+
+#[wasm_bindgen]
+impl Game {
+    /// `bot_search` for JS, seeded from the clock.
+    pub fn bot_move(&self, level: u8) -> Result<JsValue, ChessError> {
+        to_js(&self.bot_search(level, now_ms().to_bits())?)
+    }
+}
+
+impl Game {
+    /// A move for a bot playing at `level`, or `None` when there's no legal move. The same seed
+    /// gives the same move, time limits aside.
+    pub fn bot_search(&self, level: u8, seed: u64) -> Result<Option<SearchResult>, ChessError> {
+        let difficulty = Difficulty::level(level)?;
+        let limits = SearchLimits {
+            depth: Some(difficulty.depth),
+            nodes: None,
+            time_ms: Some(difficulty.time_ms),
+        };
+        if difficulty.margin == 0 && difficulty.blunder_chance == 0.0 {
+            return self.search(limits);
+        }
+
+        self.king_square(White)?;
+        self.king_square(Black)?;
+        let mut rng = Rng(seed);
+        let mut search = Search::new(limits);
+        let (scored, depth) = search.score_root_moves(self);
+        let Some(&(_, best_score)) = scored.first() else {
+            return Ok(None);
+        };
+
+        let candidates = if rng.chance(difficulty.blunder_chance) {
+            &scored[..]
+        } else {
+            let close = scored
+                .iter()
+                .take_while(|(_, score)| *score >= best_score - difficulty.margin)
+                .count();
+            &scored[..close]
+        };
+        let (mv, score) = candidates[rng.below(candidates.len())];
+        Ok(Some(self.search_result(mv, score, depth, search.nodes())))
+    }
+}
+
+/// SplitMix64, which is plenty for choosing moves and needs no dependency.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+    fn chance(&mut self, probability: f64) -> bool {
+        let unit = (self.next() >> 11) as f64 / (1u64 << 53) as f64;
+        unit < probability
+    }
+}
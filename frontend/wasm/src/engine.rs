@@ -0,0 +1,437 @@
+//! The computer opponent: iterative-deepening alpha-beta search with a quiescence search at the
+//! leaves, a transposition table keyed by Zobrist hash, and moves ordered by the table's best
+//! move, then captures by MVV-LVA, then killer moves and the history heuristic.
+
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    movegen::Move,
+    to_js, ChessError, Game,
+    PieceType::{self, *},
+    Square,
+    Team::*,
+};
+
+const MATE: i32 = 30_000;
+const INFINITY: i32 = 32_000;
+const MAX_PLY: usize = 64;
+const TABLE_SIZE: usize = 1 << 16;
+/// How many nodes pass between checks of the time and node limits.
+const CHECK_INTERVAL: u64 = 1024;
+
+/// When to stop searching. Without any limit the search goes `DEFAULT_DEPTH` moves deep; with
+/// only a time or node limit it deepens until that runs out.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct SearchLimits {
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    pub time_ms: Option<u64>,
+}
+
+impl SearchLimits {
+    const DEFAULT_DEPTH: u32 = 4;
+}
+
+/// The move to play, in the form `try_move` takes it, with what the search thought of it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SearchResult {
+    pub from: String,
+    pub to: String,
+    pub promotion: Option<String>,
+    /// Centipawns for the side to move.
+    pub score: i32,
+    pub depth: u32,
+    pub nodes: u64,
+}
+
+#[wasm_bindgen]
+impl Game {
+    /// `search` for JS, taking `{ depth, nodes, time_ms }` with any of them left out and
+    /// returning `null` when there's no legal move.
+    pub fn best_move(&self, limits: JsValue) -> Result<JsValue, ChessError> {
+        let limits = if limits.is_undefined() || limits.is_null() {
+            SearchLimits::default()
+        } else {
+            serde_wasm_bindgen::from_value(limits).map_err(|err| ChessError::InvalidLimits(err.to_string()))?
+        };
+        to_js(&self.search(limits)?)
+    }
+}
+
+impl Game {
+    /// Searches for the side to move's best move, or `None` when it has no legal move.
+    pub fn search(&self, limits: SearchLimits) -> Result<Option<SearchResult>, ChessError> {
+        // checked here so the search itself can't run into a missing king
+        self.king_square(White)?;
+        self.king_square(Black)?;
+        Ok(Search::new(limits)
+            .run(self)
+            .map(|(mv, score, depth, nodes)| self.search_result(mv, score, depth, nodes)))
+    }
+
+    pub(crate) fn search_result(&self, mv: Move, score: i32, depth: u32, nodes: u64) -> SearchResult {
+        SearchResult {
+            from: Square::from_index(mv.from).coords(),
+            to: Square::from_index(self.shown_target(mv)).coords(),
+            promotion: mv.promotion.map(|piece_type| format!("{:?}", piece_type)),
+            score,
+            depth,
+            nodes,
+        }
+    }
+
+    /// The score for the side to move.
+    fn relative_eval(&self) -> i32 {
+        let eval = self.evaluate();
+        if self.turn() == White {
+            eval
+        } else {
+            -eval
+        }
+    }
+
+    /// The piece type `mv` takes, if it's a capture.
+    fn captured(&self, mv: Move) -> Option<PieceType> {
+        if self.team_pieces(self.turn().opponent()).contains(mv.to) {
+            return self.piece_at(mv.to).map(|piece| piece.piece_type());
+        }
+        let en_passant = self.pieces(Pawn).contains(mv.from) && mv.from % 8 != mv.to % 8;
+        en_passant.then_some(Pawn)
+    }
+}
+
+fn piece_value(piece_type: PieceType) -> i32 {
+    match piece_type {
+        Pawn => 100,
+        Knight => 320,
+        Bishop => 330,
+        Rook => 500,
+        Queen => 900,
+        King => 0,
+    }
+}
+
+/// Elapsed milliseconds. `std::time::Instant` panics in the browser, so there it's `Date.now()`.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn now_ms() -> f64 {
+    #[wasm_bindgen]
+    extern "C" {
+        #[wasm_bindgen(js_namespace = Date)]
+        fn now() -> f64;
+    }
+    now()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn now_ms() -> f64 {
+    use std::{sync::OnceLock, time::Instant};
+
+    static START: OnceLock<Instant> = OnceLock::new();
+    START.get_or_init(Instant::now).elapsed().as_secs_f64() * 1000.0
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+#[derive(Clone, Copy)]
+struct Entry {
+    hash: u64,
+    depth: u32,
+    score: i32,
+    bound: Bound,
+    best: Option<Move>,
+}
+
+pub(crate) struct Search {
+    limits: SearchLimits,
+    deadline: Option<f64>,
+    depth: u32,
+    nodes: u64,
+    stopped: bool,
+    table: Vec<Option<Entry>>,
+    killers: [[Option<Move>; 2]; MAX_PLY],
+    history: Box<[[i32; 64]; 64]>,
+    /// Hashes of the positions on the current line, to score repetitions as draws.
+    path: Vec<u64>,
+    root_best: Option<Move>,
+}
+
+impl Search {
+    pub(crate) fn new(limits: SearchLimits) -> Search {
+        Search {
+            limits,
+            deadline: limits.time_ms.map(|time_ms| now_ms() + time_ms as f64),
+            depth: 0,
+            nodes: 0,
+            stopped: false,
+            table: vec![None; TABLE_SIZE],
+            killers: [[None; 2]; MAX_PLY],
+            history: Box::new([[0; 64]; 64]),
+            path: Vec::with_capacity(MAX_PLY),
+            root_best: None,
+        }
+    }
+
+    fn max_depth(&self) -> u32 {
+        match self.limits {
+            SearchLimits { depth: Some(depth), .. } => depth.clamp(1, MAX_PLY as u32 - 1),
+            SearchLimits {
+                nodes: None,
+                time_ms: None,
+                ..
+            } => SearchLimits::DEFAULT_DEPTH,
+            _ => MAX_PLY as u32 - 1,
+        }
+    }
+
+    pub(crate) fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    /// Deepens until a limit is hit, returning the best move of the last finished depth.
+    fn run(&mut self, game: &Game) -> Option<(Move, i32, u32, u64)> {
+        let mut best = None;
+        for depth in 1..=self.max_depth() {
+            self.depth = depth;
+            let score = self.negamax(game, depth, 0, -INFINITY, INFINITY);
+            if self.stopped {
+                break;
+            }
+            best = Some((self.root_best?, score, depth, self.nodes));
+            // no point looking further once a forced mate is found
+            if score.abs() >= MATE - MAX_PLY as i32 {
+                break;
+            }
+        }
+        best
+    }
+
+    /// Deepens like `run`, but searches every root move with a full window so that moves short
+    /// of the best get exact scores too. Returns the moves best first, with the last depth
+    /// finished.
+    pub(crate) fn score_root_moves(&mut self, game: &Game) -> (Vec<(Move, i32)>, u32) {
+        let moves = game.generate_legal_moves().unwrap_or_default();
+        let mut scored: Vec<(Move, i32)> = moves.into_iter().map(|mv| (mv, 0)).collect();
+        let mut finished = 0;
+        self.path.push(game.hash());
+        for depth in 1..=self.max_depth() {
+            self.depth = depth;
+            let mut rescored = Vec::with_capacity(scored.len());
+            for (mv, _) in &scored {
+                let mut child = *game;
+                child.play(*mv);
+                let score = -self.negamax(&child, depth - 1, 1, -INFINITY, INFINITY);
+                if self.stopped {
+                    break;
+                }
+                rescored.push((*mv, score));
+            }
+            if self.stopped {
+                break;
+            }
+            rescored.sort_by_key(|(_, score)| -score);
+            scored = rescored;
+            finished = depth;
+        }
+        self.path.pop();
+        (scored, finished)
+    }
+
+    /// The first depth always finishes, so there's always a move to play.
+    fn out_of_budget(&mut self) -> bool {
+        if !self.stopped && self.depth > 1 && self.nodes.is_multiple_of(CHECK_INTERVAL) {
+            let out_of_nodes = self.limits.nodes.is_some_and(|nodes| self.nodes >= nodes);
+            let out_of_time = self.deadline.is_some_and(|deadline| now_ms() >= deadline);
+            self.stopped = out_of_nodes || out_of_time;
+        }
+        self.stopped
+    }
+
+    fn probe(&self, hash: u64) -> Option<Entry> {
+        self.table[hash as usize % TABLE_SIZE].filter(|entry| entry.hash == hash)
+    }
+
+    fn store(&mut self, entry: Entry) {
+        let slot = &mut self.table[entry.hash as usize % TABLE_SIZE];
+        if slot.is_none_or(|old| old.hash != entry.hash || old.depth <= entry.depth) {
+            *slot = Some(entry);
+        }
+    }
+
+    fn negamax(&mut self, game: &Game, depth: u32, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        self.nodes += 1;
+        if self.out_of_budget() {
+            return 0;
+        }
+        let hash = game.hash();
+        if ply > 0 && self.path.contains(&hash) {
+            return 0;
+        }
+        if depth == 0 || ply >= MAX_PLY - 1 {
+            return self.quiescence(game, ply, alpha, beta);
+        }
+
+        let entry = self.probe(hash);
+        if let Some(entry) = entry.filter(|entry| ply > 0 && entry.depth >= depth) {
+            let score = from_table(entry.score, ply);
+            match entry.bound {
+                Bound::Exact => return score,
+                Bound::Lower if score >= beta => return score,
+                Bound::Upper if score <= alpha => return score,
+                _ => {}
+            }
+        }
+
+        // the king was checked for up front, so move generation can't fail
+        let mut moves = game.generate_legal_moves().unwrap_or_default();
+        if moves.is_empty() {
+            let in_check = game.team_in_check(game.turn()).unwrap_or(false);
+            return if in_check { -MATE + ply as i32 } else { 0 };
+        }
+        self.order(game, &mut moves, entry.and_then(|entry| entry.best), ply);
+
+        let original_alpha = alpha;
+        let mut best_score = -INFINITY;
+        let mut best_move = moves[0];
+        self.path.push(hash);
+        for mv in moves {
+            let mut child = *game;
+            child.play(mv);
+            let score = -self.negamax(&child, depth - 1, ply + 1, -beta, -alpha);
+            if self.stopped {
+                break;
+            }
+            if score > best_score {
+                best_score = score;
+                best_move = mv;
+            }
+            if score > alpha {
+                alpha = score;
+            }
+            if alpha >= beta {
+                if game.captured(mv).is_none() {
+                    self.remember_cutoff(mv, depth, ply);
+                }
+                break;
+            }
+        }
+        self.path.pop();
+
+        if self.stopped {
+            return 0;
+        }
+        if ply == 0 {
+            self.root_best = Some(best_move);
+        }
+        let bound = if best_score >= beta {
+            Bound::Lower
+        } else if best_score > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        self.store(Entry {
+            hash,
+            depth,
+            score: to_table(best_score, ply),
+            bound,
+            best: Some(best_move),
+        });
+        best_score
+    }
+
+    /// Plays out captures and promotions until the position is quiet, so the search doesn't stop
+    /// in the middle of an exchange.
+    fn quiescence(&mut self, game: &Game, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        self.nodes += 1;
+        if self.out_of_budget() {
+            return 0;
+        }
+        let mut moves = game.generate_legal_moves().unwrap_or_default();
+        if moves.is_empty() {
+            let in_check = game.team_in_check(game.turn()).unwrap_or(false);
+            return if in_check { -MATE + ply as i32 } else { 0 };
+        }
+
+        let stand_pat = game.relative_eval();
+        if stand_pat >= beta || ply >= MAX_PLY - 1 {
+            return stand_pat;
+        }
+        alpha = alpha.max(stand_pat);
+
+        moves.retain(|mv| game.captured(*mv).is_some() || mv.promotion == Some(Queen));
+        self.order(game, &mut moves, None, ply);
+        for mv in moves {
+            let mut child = *game;
+            child.play(mv);
+            let score = -self.quiescence(&child, ply + 1, -beta, -alpha);
+            if self.stopped {
+                return 0;
+            }
+            if score >= beta {
+                return score;
+            }
+            alpha = alpha.max(score);
+        }
+        alpha
+    }
+
+    fn order(&self, game: &Game, moves: &mut [Move], table_move: Option<Move>, ply: usize) {
+        moves.sort_by_cached_key(|mv| {
+            let score = if Some(*mv) == table_move {
+                1_000_000
+            } else if let Some(victim) = game.captured(*mv) {
+                let attacker = game.piece_at(mv.from).map_or(King, |piece| piece.piece_type());
+                100_000 + piece_value(victim) * 10 - piece_value(attacker) / 10
+            } else if let Some(promotion) = mv.promotion {
+                90_000 + piece_value(promotion)
+            } else if self.killers[ply][0] == Some(*mv) {
+                80_000
+            } else if self.killers[ply][1] == Some(*mv) {
+                79_000
+            } else {
+                self.history[mv.from][mv.to]
+            };
+            -score
+        });
+    }
+
+    /// Quiet moves that cause a cutoff are tried early in sibling positions (killers) and
+    /// anywhere else in the tree (history).
+    fn remember_cutoff(&mut self, mv: Move, depth: u32, ply: usize) {
+        if self.killers[ply][0] != Some(mv) {
+            self.killers[ply][1] = self.killers[ply][0];
+            self.killers[ply][0] = Some(mv);
+        }
+        let bonus = (depth * depth) as i32;
+        let history = &mut self.history[mv.from][mv.to];
+        *history = (*history + bonus).min(70_000);
+    }
+}
+
+/// Mate scores count plies from the root, but the table is shared between plies, so they're
+/// stored counting from the position itself.
+fn to_table(score: i32, ply: usize) -> i32 {
+    if score >= MATE - MAX_PLY as i32 {
+        score + ply as i32
+    } else if score <= -MATE + MAX_PLY as i32 {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn from_table(score: i32, ply: usize) -> i32 {
+    if score >= MATE - MAX_PLY as i32 {
+        score - ply as i32
+    } else if score <= -MATE + MAX_PLY as i32 {
+        score + ply as i32
+    } else {
+        score
+    }
+}
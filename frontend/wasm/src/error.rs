@@ -0,0 +1,75 @@
+use std::fmt::{Display, Formatter};
+
+use wasm_bindgen::prelude::*;
+
+/// Everything that can go wrong when driving a `Game`. Crossing into JS it becomes a thrown
+/// `Error` named `ChessError`, carrying the `Display` message and the variant's `code`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChessError {
+    InvalidCoordinates(String),
+    InvalidTeam(String),
+    InvalidPieceType(String),
+    InvalidRuleSet(String),
+    InvalidDump(String),
+    InvalidFen(String),
+    MissingKing(String),
+    IllegalMove { from: String, to: String },
+    InvalidPromotion(String),
+    MissingPromotion,
+    InvalidLimits(String),
+    InvalidLevel(u8),
+    Serialization(String),
+}
+
+impl ChessError {
+    /// A stable name for the variant, for callers to match on instead of the message.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ChessError::InvalidCoordinates(_) => "invalid_coordinates",
+            ChessError::InvalidTeam(_) => "invalid_team",
+            ChessError::InvalidPieceType(_) => "invalid_piece_type",
+            ChessError::InvalidRuleSet(_) => "invalid_rule_set",
+            ChessError::InvalidDump(_) => "invalid_dump",
+            ChessError::InvalidFen(_) => "invalid_fen",
+            ChessError::MissingKing(_) => "missing_king",
+            ChessError::IllegalMove { .. } => "illegal_move",
+            ChessError::InvalidPromotion(_) => "invalid_promotion",
+            ChessError::MissingPromotion => "missing_promotion",
+            ChessError::InvalidLimits(_) => "invalid_limits",
+            ChessError::InvalidLevel(_) => "invalid_level",
+            ChessError::Serialization(_) => "serialization",
+        }
+    }
+}
+
+impl Display for ChessError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            ChessError::InvalidCoordinates(coords) => write!(f, "Invalid coordinates: {}", coords),
+            ChessError::InvalidTeam(team) => write!(f, "Invalid team: {}", team),
+            ChessError::InvalidPieceType(piece_type) => write!(f, "Invalid piece type: {}", piece_type),
+            ChessError::InvalidRuleSet(rule_set) => write!(f, "Unknown rule set: {}", rule_set),
+            ChessError::InvalidDump(reason) => write!(f, "Invalid game dump: {}", reason),
+            ChessError::InvalidFen(reason) => write!(f, "Invalid FEN: {}", reason),
+            ChessError::MissingKing(team) => write!(f, "No {} king on the board", team),
+            ChessError::IllegalMove { from, to } => write!(f, "No legal move from {} to {}", from, to),
+            ChessError::InvalidPromotion(piece_type) => write!(f, "Pawns can't promote to {}", piece_type),
+            ChessError::MissingPromotion => write!(f, "Move promotes a pawn but no piece was chosen"),
+            ChessError::InvalidLimits(reason) => write!(f, "Invalid search limits: {}", reason),
+            ChessError::InvalidLevel(level) => write!(f, "Bot levels run from 1 to 10, not {}", level),
+            ChessError::Serialization(reason) => write!(f, "Failed to serialize game: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ChessError {}
+
+impl From<ChessError> for JsValue {
+    fn from(err: ChessError) -> JsValue {
+        let error = js_sys::Error::new(&err.to_string());
+        error.set_name("ChessError");
+        // setting a property on a fresh object can't fail
+        let _ = js_sys::Reflect::set(&error, &"code".into(), &err.code().into());
+        error.into()
+    }
+}
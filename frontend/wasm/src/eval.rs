@@ -0,0 +1,363 @@
+//! Static evaluation in centipawns from White's side: material, piece-square tables, pawn
+//! structure, king safety and mobility. Every term has a middlegame and an endgame weight,
+//! blended by how much material is left, and all of them live in `EvalParams` so they can be tuned
+//! without touching the code.
+
+use std::ops::{Add, AddAssign, Mul, Sub};
+
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    attacks,
+    bitboard::Bitboard,
+    Game,
+    PieceType::{self, *},
+    Team::{self, *},
+};
+
+/// A middlegame and an endgame value, blended by the game phase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Weight {
+    pub mg: i32,
+    pub eg: i32,
+}
+
+const fn w(mg: i32, eg: i32) -> Weight {
+    Weight { mg, eg }
+}
+
+impl Add for Weight {
+    type Output = Weight;
+
+    fn add(self, rhs: Weight) -> Weight {
+        w(self.mg + rhs.mg, self.eg + rhs.eg)
+    }
+}
+
+impl AddAssign for Weight {
+    fn add_assign(&mut self, rhs: Weight) {
+        *self = *self + rhs;
+    }
+}
+
+impl Mul<i32> for Weight {
+    type Output = Weight;
+
+    fn mul(self, rhs: i32) -> Weight {
+        w(self.mg * rhs, self.eg * rhs)
+    }
+}
+
+impl Sub for Weight {
+    type Output = Weight;
+
+    fn sub(self, rhs: Weight) -> Weight {
+        w(self.mg - rhs.mg, self.eg - rhs.eg)
+    }
+}
+
+/// The evaluation's weights. Arrays by piece type run King, Queen, Bishop, Knight, Rook, Pawn.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvalParams {
+    pub material: [Weight; 6],
+    /// Bonuses by square for the middlegame, laid out as the board is printed from White's side,
+    /// a8 first. Black's pieces read them mirrored.
+    pub middlegame_squares: [[i32; 64]; 6],
+    pub endgame_squares: [[i32; 64]; 6],
+    /// For each pawn with another of its own further up the file.
+    pub doubled_pawn: Weight,
+    /// For each pawn with none of its own on the neighbouring files.
+    pub isolated_pawn: Weight,
+    /// For a pawn no enemy pawn can stop, by its rank counted from its own side.
+    pub passed_pawn: [Weight; 8],
+    /// For each pawn of its own one or two ranks in front of the king, on its file or next to it.
+    pub pawn_shield: Weight,
+    /// For each square around the king the opponent attacks.
+    pub king_attack: Weight,
+    /// For each square a piece attacks that its own side doesn't hold.
+    pub mobility: [Weight; 6],
+    /// How much each piece counts towards the middlegame. The starting position is all
+    /// middlegame and bare kings all endgame.
+    pub phase: [i32; 6],
+}
+
+impl EvalParams {
+    pub const DEFAULT: EvalParams = EvalParams {
+        material: [w(0, 0), w(900, 940), w(330, 320), w(320, 300), w(500, 530), w(100, 120)],
+        middlegame_squares: [
+            KING_MIDDLEGAME,
+            QUEEN_SQUARES,
+            BISHOP_SQUARES,
+            KNIGHT_SQUARES,
+            ROOK_SQUARES,
+            PAWN_MIDDLEGAME,
+        ],
+        endgame_squares: [
+            KING_ENDGAME,
+            QUEEN_SQUARES,
+            BISHOP_SQUARES,
+            KNIGHT_SQUARES,
+            ROOK_SQUARES,
+            PAWN_ENDGAME,
+        ],
+        doubled_pawn: w(-10, -20),
+        isolated_pawn: w(-15, -10),
+        passed_pawn: [
+            w(0, 0),
+            w(5, 10),
+            w(10, 20),
+            w(15, 35),
+            w(25, 60),
+            w(40, 100),
+            w(60, 150),
+            w(0, 0),
+        ],
+        pawn_shield: w(12, 0),
+        king_attack: w(-10, -2),
+        mobility: [w(0, 0), w(1, 2), w(5, 5), w(4, 4), w(2, 4), w(0, 0)],
+        phase: [0, 4, 1, 1, 2, 0],
+    };
+}
+
+impl Default for EvalParams {
+    fn default() -> EvalParams {
+        EvalParams::DEFAULT
+    }
+}
+
+#[rustfmt::skip]
+const PAWN_MIDDLEGAME: [i32; 64] = [
+     0,  0,   0,   0,   0,   0,  0,  0,
+    50, 50,  50,  50,  50,  50, 50, 50,
+    10, 10,  20,  30,  30,  20, 10, 10,
+     5,  5,  10,  25,  25,  10,  5,  5,
+     0,  0,   0,  20,  20,   0,  0,  0,
+     5, -5, -10,   0,   0, -10, -5,  5,
+     5, 10,  10, -20, -20,  10, 10,  5,
+     0,  0,   0,   0,   0,   0,  0,  0,
+];
+
+#[rustfmt::skip]
+const PAWN_ENDGAME: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    60, 60, 60, 60, 60, 60, 60, 60,
+    40, 40, 40, 40, 40, 40, 40, 40,
+    25, 25, 25, 25, 25, 25, 25, 25,
+    15, 15, 15, 15, 15, 15, 15, 15,
+     5,  5,  5,  5,  5,  5,  5,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const KNIGHT_SQUARES: [i32; 64] = [
+    -50, -40, -30, -30, -30, -30, -40, -50,
+    -40, -20,   0,   0,   0,   0, -20, -40,
+    -30,   0,  10,  15,  15,  10,   0, -30,
+    -30,   5,  15,  20,  20,  15,   5, -30,
+    -30,   0,  15,  20,  20,  15,   0, -30,
+    -30,   5,  10,  15,  15,  10,   5, -30,
+    -40, -20,   0,   5,   5,   0, -20, -40,
+    -50, -40, -30, -30, -30, -30, -40, -50,
+];
+
+#[rustfmt::skip]
+const BISHOP_SQUARES: [i32; 64] = [
+    -20, -10, -10, -10, -10, -10, -10, -20,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -10,   0,   5,  10,  10,   5,   0, -10,
+    -10,   5,   5,  10,  10,   5,   5, -10,
+    -10,   0,  10,  10,  10,  10,   0, -10,
+    -10,  10,  10,  10,  10,  10,  10, -10,
+    -10,   5,   0,   0,   0,   0,   5, -10,
+    -20, -10, -10, -10, -10, -10, -10, -20,
+];
+
+#[rustfmt::skip]
+const ROOK_SQUARES: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+     5, 10, 10, 10, 10, 10, 10,  5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+     0,  0,  0,  5,  5,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const QUEEN_SQUARES: [i32; 64] = [
+    -20, -10, -10, -5, -5, -10, -10, -20,
+    -10,   0,   0,  0,  0,   0,   0, -10,
+    -10,   0,   5,  5,  5,   5,   0, -10,
+     -5,   0,   5,  5,  5,   5,   0,  -5,
+      0,   0,   5,  5,  5,   5,   0,  -5,
+    -10,   5,   5,  5,  5,   5,   0, -10,
+    -10,   0,   5,  0,  0,   0,   0, -10,
+    -20, -10, -10, -5, -5, -10, -10, -20,
+];
+
+#[rustfmt::skip]
+const KING_MIDDLEGAME: [i32; 64] = [
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -20, -30, -30, -40, -40, -30, -30, -20,
+    -10, -20, -20, -20, -20, -20, -20, -10,
+     20,  20,   0,   0,   0,   0,  20,  20,
+     20,  30,  10,   0,   0,  10,  30,  20,
+];
+
+#[rustfmt::skip]
+const KING_ENDGAME: [i32; 64] = [
+    -50, -40, -30, -20, -20, -30, -40, -50,
+    -30, -20, -10,   0,   0, -10, -20, -30,
+    -30, -10,  20,  30,  30,  20, -10, -30,
+    -30, -10,  30,  40,  40,  30, -10, -30,
+    -30, -10,  30,  40,  40,  30, -10, -30,
+    -30, -10,  20,  30,  30,  20, -10, -30,
+    -30, -30,   0,   0,   0,   0, -30, -30,
+    -50, -30, -30, -30, -30, -30, -30, -50,
+];
+
+#[wasm_bindgen]
+impl Game {
+    /// `evaluate_with` the default weights, for an evaluation bar.
+    pub fn evaluate(&self) -> i32 {
+        self.evaluate_with(&EvalParams::DEFAULT)
+    }
+}
+
+impl Game {
+    /// Centipawns from White's side, with no search: positive when White is better.
+    pub fn evaluate_with(&self, params: &EvalParams) -> i32 {
+        let (white, white_attacks) = self.piece_score(White, params);
+        let (black, black_attacks) = self.piece_score(Black, params);
+        let score = white - black + self.pawn_score(White, params) - self.pawn_score(Black, params)
+            + self.king_safety(White, black_attacks, params)
+            - self.king_safety(Black, white_attacks, params);
+
+        // both sides' starting pieces
+        let opening_phase = 2
+            * (params.phase[King as usize]
+                + params.phase[Queen as usize]
+                + 2 * (params.phase[Bishop as usize] + params.phase[Knight as usize] + params.phase[Rook as usize])
+                + 8 * params.phase[Pawn as usize]);
+        if opening_phase == 0 {
+            return score.eg;
+        }
+        let phase = PieceType::ALL
+            .into_iter()
+            .map(|piece_type| params.phase[piece_type as usize] * self.pieces(piece_type).into_iter().count() as i32)
+            .sum::<i32>()
+            .min(opening_phase);
+        (score.mg * phase + score.eg * (opening_phase - phase)) / opening_phase
+    }
+
+    /// Material, square bonuses and mobility for `team`'s pieces, along with every square they
+    /// attack.
+    fn piece_score(&self, team: Team, params: &EvalParams) -> (Weight, Bitboard) {
+        let own = self.team_pieces(team);
+        let occupied = self.occupied();
+        // against a bare king a queen or rook wins from anywhere, and only how far the bare king
+        // is driven to the edge (its own table) matters. Placement and mobility on the winning
+        // side would reward a quiet king move played ahead of a promotion, with the promotion
+        // left to the quiescence search, over promoting straight away.
+        let enemy = self.team_pieces(team.opponent());
+        let bare_king = enemy == enemy & self.pieces(King);
+        let mopping_up = bare_king && !(own & (self.pieces(Queen) | self.pieces(Rook))).is_empty();
+        let mut score = Weight::default();
+        let mut attacked = Bitboard::EMPTY;
+        for piece_type in PieceType::ALL {
+            let index = piece_type as usize;
+            for sq in own & self.pieces(piece_type) {
+                let table_sq = if team == White { sq ^ 56 } else { sq };
+                let piece_attacks = match piece_type {
+                    King => attacks::king(sq),
+                    Queen => attacks::bishop(sq, occupied) | attacks::rook(sq, occupied),
+                    Bishop => attacks::bishop(sq, occupied),
+                    Knight => attacks::knight(sq),
+                    Rook => attacks::rook(sq, occupied),
+                    Pawn => attacks::pawn(team, sq),
+                };
+                let mobility = (piece_attacks & !own).into_iter().count() as i32;
+                score += params.material[index];
+                if !mopping_up {
+                    score += w(
+                        params.middlegame_squares[index][table_sq],
+                        params.endgame_squares[index][table_sq],
+                    ) + params.mobility[index] * mobility;
+                }
+                attacked |= piece_attacks;
+            }
+        }
+        (score, attacked)
+    }
+
+    fn pawn_score(&self, team: Team, params: &EvalParams) -> Weight {
+        let pawns = self.team_pieces(team) & self.pieces(Pawn);
+        let enemy_pawns = self.team_pieces(team.opponent()) & self.pieces(Pawn);
+        let mut score = Weight::default();
+        for sq in pawns {
+            let (file, ahead) = (sq % 8, ranks_ahead(team, sq / 8));
+            if !(pawns & file_mask(file) & ahead).is_empty() {
+                score += params.doubled_pawn;
+            }
+            if (pawns & neighbour_files(file)).is_empty() {
+                score += params.isolated_pawn;
+            }
+            if (enemy_pawns & (file_mask(file) | neighbour_files(file)) & ahead).is_empty() {
+                score += params.passed_pawn[relative_rank(team, sq)];
+            }
+        }
+        score
+    }
+
+    fn king_safety(&self, team: Team, enemy_attacks: Bitboard, params: &EvalParams) -> Weight {
+        let Some(king_sq) = (self.team_pieces(team) & self.pieces(King)).first() else {
+            return Weight::default();
+        };
+        let attacked = (attacks::king(king_sq) & enemy_attacks).into_iter().count() as i32;
+        let shield = (self.team_pieces(team) & self.pieces(Pawn))
+            .into_iter()
+            .filter(|&sq| {
+                let ranks_in_front = relative_rank(team, sq) as i32 - relative_rank(team, king_sq) as i32;
+                (sq % 8).abs_diff(king_sq % 8) <= 1 && (1..=2).contains(&ranks_in_front)
+            })
+            .count() as i32;
+        params.king_attack * attacked + params.pawn_shield * shield
+    }
+}
+
+fn file_mask(file: usize) -> Bitboard {
+    Bitboard(0x0101_0101_0101_0101 << file)
+}
+
+fn neighbour_files(file: usize) -> Bitboard {
+    let mut files = Bitboard::EMPTY;
+    if file > 0 {
+        files |= file_mask(file - 1);
+    }
+    if file < 7 {
+        files |= file_mask(file + 1);
+    }
+    files
+}
+
+/// Every square on the ranks in front of `rank`, as `team`'s pawns move.
+fn ranks_ahead(team: Team, rank: usize) -> Bitboard {
+    match team {
+        White if rank == 7 => Bitboard::EMPTY,
+        White => Bitboard(!0 << ((rank + 1) * 8)),
+        Black => Bitboard((1 << (rank * 8)) - 1),
+    }
+}
+
+/// The rank of `sq` counted from `team`'s side of the board.
+fn relative_rank(team: Team, sq: usize) -> usize {
+    match team {
+        White => sq / 8,
+        Black => 7 - sq / 8,
+    }
+}
@@ -0,0 +1,129 @@
+//! Loading positions from Forsyth–Edwards Notation. Castling rights may be given as `KQkq` or,
+//! for Chess960, as the files of the castling rooks (Shredder-FEN, e.g. `HAha`). Rights with a
+//! king or rook off the standard squares make it a `Shuffled` game.
+
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    bitboard::Bitboard, castling::CastlingRights, ChessError, Game, Piece, PieceType::*, RuleSet::*, Square, Team::*,
+};
+
+#[wasm_bindgen]
+impl Game {
+    pub fn from_fen(fen: &str) -> Result<Game, ChessError> {
+        let invalid = |reason: &str| ChessError::InvalidFen(format!("{} in \"{}\"", reason, fen));
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        let [placement, turn, castling, en_passant, counters @ ..] = fields.as_slice() else {
+            return Err(invalid("missing fields"));
+        };
+
+        let mut game = Game {
+            pieces: [Bitboard::EMPTY; 6],
+            teams: [Bitboard::EMPTY; 2],
+            castling: CastlingRights::default(),
+            en_passant_pawn: None,
+            turn: White,
+            move_num: 0,
+            rule_set: Standard,
+        };
+
+        let ranks: Vec<&str> = placement.split('/').collect();
+        if ranks.len() != 8 {
+            return Err(invalid("board needs 8 ranks"));
+        }
+        for (rank_index, rank) in ranks.iter().rev().enumerate() {
+            let mut file = 0;
+            for piece_char in rank.chars() {
+                if let Some(empty) = piece_char.to_digit(10) {
+                    if !(1..=8).contains(&empty) {
+                        return Err(invalid("empty squares run from 1 to 8"));
+                    }
+                    file += empty as usize;
+                    continue;
+                }
+                if file > 7 {
+                    return Err(invalid("rank doesn't have 8 files"));
+                }
+                let piece = piece_from_char(piece_char).ok_or_else(|| invalid("unknown piece"))?;
+                game.put(Square::new(rank_index, file).index(), piece);
+                file += 1;
+            }
+            if file != 8 {
+                return Err(invalid("rank doesn't have 8 files"));
+            }
+        }
+        for team in [White, Black] {
+            let kings = game.team_pieces(team) & game.pieces(King);
+            if kings.is_empty() || kings.more_than_one() {
+                return Err(invalid("each side needs exactly one king"));
+            }
+        }
+
+        game.turn = match *turn {
+            "w" => White,
+            "b" => Black,
+            _ => return Err(invalid("side to move must be w or b")),
+        };
+
+        if *castling != "-" {
+            for right in castling.chars() {
+                let team = if right.is_ascii_uppercase() { White } else { Black };
+                let king_sq = game.king_square(team)?;
+                let back_rank = if team.is_white() { 0 } else { 7 };
+                if king_sq / 8 != back_rank {
+                    return Err(invalid("castling right for a king off its back rank"));
+                }
+                let mut rooks = game.team_pieces(team) & game.pieces(Rook) & Bitboard::rank(back_rank);
+                // K and Q name the outermost rook on that side, as in X-FEN
+                let rook_sq = match right.to_ascii_uppercase() {
+                    'K' => rooks.filter(|sq| *sq > king_sq).last(),
+                    'Q' => rooks.find(|sq| *sq < king_sq),
+                    file @ 'A'..='H' => rooks.find(|sq| sq % 8 == file as usize - 'A' as usize),
+                    _ => return Err(invalid("unknown castling right")),
+                }
+                .ok_or_else(|| invalid("castling right without a rook"))?;
+                game.castling.grant(team, rook_sq > king_sq, rook_sq);
+                if king_sq % 8 != 4 || !matches!(rook_sq % 8, 0 | 7) {
+                    game.rule_set = Shuffled;
+                }
+            }
+        }
+
+        if *en_passant != "-" {
+            // FEN names the square passed over, the game tracks the pawn that passed it
+            let target = Square::from(en_passant).map_err(|_| invalid("bad en passant square"))?;
+            let (target_rank, pawn_rank) = if game.turn.is_white() { (5, 4) } else { (2, 3) };
+            if target.rank() != target_rank {
+                return Err(invalid("en passant square is on the wrong rank"));
+            }
+            let pawn_sq = Square::new(pawn_rank, target.file());
+            let pushed = game.team_pieces(game.turn.opponent()) & game.pieces(Pawn);
+            if !pushed.contains(pawn_sq.index()) {
+                return Err(invalid("en passant square without a pawn that passed it"));
+            }
+            game.en_passant_pawn = Some(pawn_sq);
+        }
+
+        let full_moves = match counters.get(1) {
+            Some(full_moves) => full_moves.parse::<u32>().map_err(|_| invalid("bad move number"))?,
+            None => 1,
+        };
+        game.move_num = full_moves.saturating_sub(1) * 2 + u32::from(!game.turn.is_white());
+
+        Ok(game)
+    }
+}
+
+fn piece_from_char(piece_char: char) -> Option<Piece> {
+    let piece_type = match piece_char.to_ascii_lowercase() {
+        'k' => King,
+        'q' => Queen,
+        'b' => Bishop,
+        'n' => Knight,
+        'r' => Rook,
+        'p' => Pawn,
+        _ => return None,
+    };
+    let team = if piece_char.is_ascii_uppercase() { White } else { Black };
+    Some(Piece::new(piece_type, team))
+}
@@ -1,20 +1,29 @@
 use std::fmt::{Display, Formatter};
 
+use bitboard::Bitboard;
+use castling::{castling_targets, CastlingRights};
 use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
 use PieceType::*;
 use RuleSet::*;
 use Team::*;
 
-#[wasm_bindgen]
-extern "C" {
-    #[wasm_bindgen(js_namespace = console)]
-    fn log(s: &str);
-}
-
-macro_rules! console_log {
-    ($($t:tt)*) => (log(&format_args!($($t)*).to_string()))
-}
+pub use difficulty::Difficulty;
+pub use engine::{SearchLimits, SearchResult};
+pub use error::ChessError;
+pub use eval::{EvalParams, Weight};
+
+mod attacks;
+mod bitboard;
+mod castling;
+mod difficulty;
+mod engine;
+mod error;
+mod eval;
+mod fen;
+mod movegen;
+mod perft;
+mod zobrist;
 
 fn get_default_board() -> [[Option<Piece>; 8]; 8] {
     [
@@ -53,9 +62,25 @@ enum RuleSet {
     Shuffled,
 }
 
+/// A position and its game state. Pieces are kept as bitboards, one set of squares per piece type
+/// and per team, but dumps and snapshots still use the board layout of `GameDump`.
 #[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(try_from = "GameDump", into = "GameDump")]
 #[wasm_bindgen]
 pub struct Game {
+    pieces: [Bitboard; 6],
+    teams: [Bitboard; 2],
+    castling: CastlingRights,
+    en_passant_pawn: Option<Square>,
+    turn: Team,
+    move_num: u32,
+    rule_set: RuleSet,
+}
+
+/// The serialized form of a `Game`, readable by `from_dump` in older clients. Dumps from before
+/// `castling_rooks` only have the moved flags, which are still written alongside it.
+#[derive(Serialize, Deserialize)]
+struct GameDump {
     board: [[Option<Piece>; 8]; 8],
     white_king_moved: bool,
     white_rook_a_moved: bool,
@@ -63,12 +88,122 @@ pub struct Game {
     black_king_moved: bool,
     black_rook_a_moved: bool,
     black_rook_h_moved: bool,
+    #[serde(default)]
+    castling_rooks: Option<CastlingRights>,
     en_passant_pawn: Option<Square>,
     turn: Team,
     move_num: u32,
     rule_set: RuleSet,
 }
 
+/// Dumps come from clients, so the castling rooks and en passant pawn are checked against the
+/// board here; move generation takes them on trust.
+impl TryFrom<GameDump> for Game {
+    type Error = ChessError;
+
+    fn try_from(dump: GameDump) -> Result<Game, ChessError> {
+        let mut game = Game {
+            pieces: [Bitboard::EMPTY; 6],
+            teams: [Bitboard::EMPTY; 2],
+            castling: CastlingRights::default(),
+            en_passant_pawn: dump.en_passant_pawn,
+            turn: dump.turn,
+            move_num: dump.move_num,
+            rule_set: dump.rule_set,
+        };
+        for (rank_index, rank) in dump.board.iter().enumerate() {
+            for (file_index, piece) in rank.iter().enumerate() {
+                if let Some(piece) = piece {
+                    game.put(Square::new(rank_index, file_index).index(), *piece);
+                }
+            }
+        }
+        game.castling = dump.castling_rooks.unwrap_or_else(|| {
+            let mut castling = CastlingRights::default();
+            let flags = [
+                (
+                    White,
+                    dump.white_king_moved,
+                    dump.white_rook_a_moved,
+                    dump.white_rook_h_moved,
+                ),
+                (
+                    Black,
+                    dump.black_king_moved,
+                    dump.black_rook_a_moved,
+                    dump.black_rook_h_moved,
+                ),
+            ];
+            for (team, king_moved, rook_a_moved, rook_h_moved) in flags {
+                let back_rank = if team.is_white() { 0 } else { 56 };
+                let on_square = |sq: usize, piece_type| game.piece_at(sq) == Some(Piece::new(piece_type, team));
+                if king_moved || !on_square(back_rank + 4, King) {
+                    continue;
+                }
+                // the flags missed captured rooks, so the rook has to be checked for as well
+                for (rook_moved, h_side, rook_sq) in
+                    [(rook_a_moved, false, back_rank), (rook_h_moved, true, back_rank + 7)]
+                {
+                    if !rook_moved && on_square(rook_sq, Rook) {
+                        castling.grant(team, h_side, rook_sq);
+                    }
+                }
+            }
+            castling
+        });
+
+        let invalid = |reason: &str| ChessError::InvalidDump(reason.to_string());
+        for team in [White, Black] {
+            let back_rank = if team.is_white() { 0 } else { 7 };
+            for h_side in [false, true] {
+                let Some(rook_sq) = game.castling.rook(team, h_side) else {
+                    continue;
+                };
+                if rook_sq >= 64 || rook_sq / 8 != back_rank || game.piece_at(rook_sq) != Some(Piece::new(Rook, team)) {
+                    return Err(invalid("castling rook isn't a rook of its own side on the back rank"));
+                }
+                let king_sq = game
+                    .king_square(team)
+                    .map_err(|_| invalid("castling rights without a king"))?;
+                if king_sq / 8 != back_rank || (rook_sq > king_sq) != h_side {
+                    return Err(invalid("castling rook isn't on its side of the king"));
+                }
+            }
+        }
+        if let Some(pawn) = game.en_passant_pawn {
+            let pushed_to_rank = if game.turn.is_white() { 4 } else { 3 };
+            if pawn.rank() != pushed_to_rank
+                || pawn.file() >= 8
+                || game.piece_at(pawn.index()) != Some(Piece::new(Pawn, game.turn.opponent()))
+            {
+                return Err(invalid(
+                    "en passant pawn isn't an enemy pawn that has just pushed two squares",
+                ));
+            }
+        }
+        Ok(game)
+    }
+}
+
+impl From<Game> for GameDump {
+    fn from(game: Game) -> GameDump {
+        GameDump {
+            board: game.get_board(),
+            white_king_moved: game.castling.rooks(White).next().is_none(),
+            white_rook_a_moved: game.castling.rook(White, false).is_none(),
+            white_rook_h_moved: game.castling.rook(White, true).is_none(),
+            black_king_moved: game.castling.rooks(Black).next().is_none(),
+            black_rook_a_moved: game.castling.rook(Black, false).is_none(),
+            black_rook_h_moved: game.castling.rook(Black, true).is_none(),
+            castling_rooks: Some(game.castling),
+            en_passant_pawn: game.en_passant_pawn,
+            turn: game.turn,
+            move_num: game.move_num,
+            rule_set: game.rule_set,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Copy, Serialize, Deserialize)]
 struct Piece {
     piece_type: PieceType,
@@ -98,7 +233,10 @@ enum PieceType {
 }
 
 impl PieceType {
-    fn from(piece_type_str: &str) -> Result<PieceType, String> {
+    const ALL: [PieceType; 6] = [King, Queen, Bishop, Knight, Rook, Pawn];
+    const PROMOTIONS: [PieceType; 4] = [Queen, Rook, Bishop, Knight];
+
+    fn from(piece_type_str: &str) -> Result<PieceType, ChessError> {
         match piece_type_str {
             "King" => Ok(King),
             "Queen" => Ok(Queen),
@@ -106,33 +244,43 @@ impl PieceType {
             "Knight" => Ok(Knight),
             "Rook" => Ok(Rook),
             "Pawn" => Ok(Pawn),
-            _ => Err(piece_type_str.to_string()),
+            _ => Err(ChessError::InvalidPieceType(piece_type_str.to_string())),
+        }
+    }
+    /// The piece a pawn reaching the last rank turns into.
+    fn promotion(piece_type_str: &str) -> Result<PieceType, ChessError> {
+        match PieceType::from(piece_type_str)? {
+            King | Pawn => Err(ChessError::InvalidPromotion(piece_type_str.to_string())),
+            piece_type => Ok(piece_type),
         }
     }
 }
 
 impl Team {
-    fn from(team_str: &str) -> Result<Team, String> {
+    fn from(team_str: &str) -> Result<Team, ChessError> {
         match team_str {
             "Black" => Ok(Black),
             "White" => Ok(White),
-            _ => Err(team_str.to_string()),
+            _ => Err(ChessError::InvalidTeam(team_str.to_string())),
+        }
+    }
+    fn opponent(&self) -> Team {
+        match self {
+            White => Black,
+            Black => White,
         }
     }
     fn is_white(&self) -> bool {
         self == &White
     }
-    fn is_black(&self) -> bool {
-        self == &Black
-    }
 }
 
 impl RuleSet {
-    fn from(rule_set_str: &str) -> Result<RuleSet, String> {
+    fn from(rule_set_str: &str) -> Result<RuleSet, ChessError> {
         match rule_set_str {
             "standard" => Ok(Standard),
             "shuffled" => Ok(Shuffled),
-            _ => Err(rule_set_str.to_string()),
+            _ => Err(ChessError::InvalidRuleSet(rule_set_str.to_string())),
         }
     }
 }
@@ -188,56 +336,41 @@ impl Square {
     fn new(rank: usize, file: usize) -> Square {
         Square { rank, file }
     }
-    fn from_usize(rank: usize, file: usize) -> Result<Square, String> {
-        if rank > 7 || file > 7 {
-            return Err("Error: Invalid Square".to_string());
-        };
-        Ok(Square { rank, file })
+    /// The square's bit in a `Bitboard`.
+    fn index(&self) -> usize {
+        self.rank() * 8 + self.file()
     }
-    fn from_i32(rank: i32, file: i32) -> Result<Square, String> {
-        if rank > 7 || file > 7 || rank < 0 || file < 0 {
-            return Err("Error: Invalid Square".to_string());
-        };
-        let rank = rank as usize;
-        let file = file as usize;
-        Ok(Square { rank, file })
+    fn from_index(index: usize) -> Square {
+        Square::new(index / 8, index % 8)
     }
-    fn from(coords: &str) -> Result<Square, String> {
-        if coords.chars().count() != 2 {
-            return Err("Error: Invalid Coordinates".to_string());
-        }
+    fn from(coords: &str) -> Result<Square, ChessError> {
+        let invalid = || ChessError::InvalidCoordinates(coords.to_string());
         let mut chars = coords.chars();
-        let file_str = chars.next().unwrap();
-        let rank_str = chars.next().unwrap();
-        let file_strs = ['a', 'b', 'c', 'd', 'e', 'f', 'g', 'h'];
-        let file = match file_strs.iter().position(|&ele| ele == file_str) {
-            Some(file) => file,
-            None => return Err("Error: Invalid Coordinates".to_string()),
+        let (Some(file_str), Some(rank_str), None) = (chars.next(), chars.next(), chars.next()) else {
+            return Err(invalid());
         };
-        let rank = match rank_str.to_string().parse::<usize>() {
-            Ok(rank) => rank - 1,
-            Err(_) => return Err("Error: Invalid Coordinates".to_string()),
+        let file_strs = ['a', 'b', 'c', 'd', 'e', 'f', 'g', 'h'];
+        let file = file_strs.iter().position(|&ele| ele == file_str).ok_or_else(invalid)?;
+        let rank = match rank_str.to_digit(10) {
+            Some(rank @ 1..=8) => rank as usize - 1,
+            _ => return Err(invalid()),
         };
-        Square::from_usize(rank, file)
+        Ok(Square::new(rank, file))
     }
     fn coords(&self) -> String {
         self.to_string()
     }
-    fn rank_diff(&self, target_sq: Square) -> usize {
-        (self.rank() as i32 - target_sq.rank() as i32).unsigned_abs() as usize
-    }
-    fn file_diff(&self, target_sq: Square) -> usize {
-        (self.file() as i32 - target_sq.file() as i32).unsigned_abs() as usize
-    }
 }
 
 #[wasm_bindgen]
 impl Game {
     fn get_board(&self) -> [[Option<Piece>; 8]; 8] {
-        self.board
-    }
-    fn get_piece(&self, square: Square) -> Option<Piece> {
-        self.get_board()[square.rank()][square.file()]
+        let mut board = [[None; 8]; 8];
+        for sq in self.occupied() {
+            let square = Square::from_index(sq);
+            board[square.rank()][square.file()] = self.piece_at(sq);
+        }
+        board
     }
     fn turn(&self) -> Team {
         self.turn
@@ -245,315 +378,14 @@ impl Game {
     fn rule_set(&self) -> RuleSet {
         self.rule_set
     }
-    fn find_team_pieces(&self, team: Team) -> Vec<Square> {
-        let mut pieces = vec![];
-        for (rank_index, rank) in self.get_board().iter().enumerate() {
-            for (file_index, piece) in rank.iter().enumerate() {
-                if piece.is_some() && piece.unwrap().team() == team {
-                    pieces.push(Square::new(rank_index, file_index))
-                }
-            }
-        }
-        pieces
-    }
-    fn find_king(&self, team: Team) -> Square {
-        for (rank_index, rank) in self.get_board().iter().enumerate() {
-            for (file_index, piece) in rank.iter().enumerate() {
-                if piece.is_some() && piece.unwrap().is_king() && piece.unwrap().team() == team {
-                    return Square::new(rank_index, file_index);
-                }
-            }
-        }
-        panic!("Didn't find {:?} king", team)
-    }
-    fn get_diagonal_moves(&self, start_sq: Square) -> Vec<Square> {
-        let mut potential_moves = vec![];
-        let start_rank = start_sq.rank() as i32;
-        let start_file = start_sq.file() as i32;
-        //move forward-right
-        for distance in 1..8 {
-            let new_sq = Square::from_i32(start_rank + distance, start_file + distance);
-            if new_sq.is_ok() {
-                potential_moves.push(new_sq.clone().unwrap());
-            }
-            if new_sq.is_err() || self.get_piece(new_sq.unwrap()).is_some() {
-                break;
-            }
-        }
-        //move forward-left
-        for distance in 1..8 {
-            let new_sq = Square::from_i32(start_rank + distance, start_file - distance);
-            if new_sq.is_ok() {
-                potential_moves.push(new_sq.clone().unwrap());
-            }
-            if new_sq.is_err() || self.get_piece(new_sq.unwrap()).is_some() {
-                break;
-            }
-        }
-        //move backward-right
-        for distance in 1..8 {
-            let new_sq = Square::from_i32(start_rank - distance, start_file + distance);
-            if new_sq.is_ok() {
-                potential_moves.push(new_sq.clone().unwrap());
-            }
-            if new_sq.is_err() || self.get_piece(new_sq.unwrap()).is_some() {
-                break;
-            }
-        }
-        //move backward-left
-        for distance in 1..8 {
-            let new_sq = Square::from_i32(start_rank - distance, start_file - distance);
-            if new_sq.is_ok() {
-                potential_moves.push(new_sq.clone().unwrap());
-            }
-            if new_sq.is_err() || self.get_piece(new_sq.unwrap()).is_some() {
-                break;
-            }
-        }
-        potential_moves
-    }
-    fn get_straight_moves(&self, start_sq: Square) -> Vec<Square> {
-        let mut potential_moves = vec![];
-        let start_rank = start_sq.rank() as i32;
-        let start_file = start_sq.file() as i32;
-        // move right
-        for distance in 1..8 {
-            let new_sq = Square::from_i32(start_rank + distance, start_file);
-            if new_sq.is_ok() {
-                potential_moves.push(new_sq.clone().unwrap());
-            }
-            if new_sq.is_err() || self.get_piece(new_sq.unwrap()).is_some() {
-                break;
-            }
-        }
-        //move left
-        for distance in 1..8 {
-            let new_sq = Square::from_i32(start_rank - distance, start_file);
-            if new_sq.is_ok() {
-                potential_moves.push(new_sq.clone().unwrap());
-            }
-            if new_sq.is_err() || self.get_piece(new_sq.unwrap()).is_some() {
-                break;
-            }
-        }
-        // move forward
-        for distance in 1..8 {
-            let new_sq = Square::from_i32(start_rank, start_file + distance);
-            if new_sq.is_ok() {
-                potential_moves.push(new_sq.clone().unwrap());
-            }
-            if new_sq.is_err() || self.get_piece(new_sq.unwrap()).is_some() {
-                break;
-            }
-        }
-        //move backward
-        for distance in 1..8 {
-            let new_sq = Square::from_i32(start_rank, start_file - distance);
-            if new_sq.is_ok() {
-                potential_moves.push(new_sq.clone().unwrap());
-            }
-            if new_sq.is_err() || self.get_piece(new_sq.unwrap()).is_some() {
-                break;
-            }
-        }
-        potential_moves
-    }
-    fn get_king_moves(&self, start_sq: Square) -> Vec<Square> {
-        let team = self.get_piece(start_sq).unwrap().team();
-        let start_rank = start_sq.rank() as i32;
-        let start_file = start_sq.file() as i32;
-
-        let mut potential_moves = vec![];
-        for rank_movement in [-1, 0, 1] {
-            for file_movement in [-1, 0, 1] {
-                if rank_movement == 0 && file_movement == 0 {
-                    continue;
-                }
-                let new_rank = start_rank + rank_movement;
-                let new_file = start_file + file_movement;
-                let new_sq = Square::from_i32(new_rank, new_file);
-                if let Ok(new_sq) = new_sq {
-                    potential_moves.push(new_sq)
-                }
-            }
-        }
-
-        // castle
-        if team.is_white() && !self.white_king_moved {
-            if !self.white_rook_a_moved
-                && self.get_piece(Square::from("c1").unwrap()).is_none()
-                && self.get_piece(Square::from("d1").unwrap()).is_none()
-            {
-                potential_moves.push(Square::from("c1").unwrap());
-            }
-            if !self.white_rook_h_moved
-                && self.get_piece(Square::from("f1").unwrap()).is_none()
-                && self.get_piece(Square::from("g1").unwrap()).is_none()
-            {
-                potential_moves.push(Square::from("g1").unwrap());
-            }
-        } else if team.is_black() && !self.black_king_moved {
-            if !self.black_rook_a_moved
-                && self.get_piece(Square::from("c8").unwrap()).is_none()
-                && self.get_piece(Square::from("d8").unwrap()).is_none()
-            {
-                potential_moves.push(Square::from("c8").unwrap());
-            }
-            if !self.black_rook_h_moved
-                && self.get_piece(Square::from("f8").unwrap()).is_none()
-                && self.get_piece(Square::from("g8").unwrap()).is_none()
-            {
-                potential_moves.push(Square::from("g8").unwrap());
-            }
-        }
-
-        potential_moves
-    }
-    fn get_queen_moves(&self, start_sq: Square) -> Vec<Square> {
-        let mut diagonal_moves = self.get_diagonal_moves(start_sq);
-        let mut straight_moves = self.get_straight_moves(start_sq);
-        diagonal_moves.append(&mut straight_moves);
-        diagonal_moves
-    }
-    fn get_bishop_moves(&self, start_sq: Square) -> Vec<Square> {
-        self.get_diagonal_moves(start_sq)
-    }
-    fn get_knight_moves(&self, start_sq: Square) -> Vec<Square> {
-        let mut potential_moves = vec![];
-        let start_rank = start_sq.rank() as i32;
-        let start_file = start_sq.file() as i32;
-
-        for rank_diff in [-2_i32, -1, 1, 2] {
-            for file_diff in [-2_i32, -1, 1, 2] {
-                if rank_diff.abs() + file_diff.abs() == 3 {
-                    let new_sq = Square::from_i32(start_rank + rank_diff, start_file + file_diff);
-                    if let Ok(new_sq) = new_sq {
-                        potential_moves.push(new_sq)
-                    }
-                }
-            }
-        }
-
-        potential_moves
-    }
-    fn get_rook_moves(&self, start_sq: Square) -> Vec<Square> {
-        self.get_straight_moves(start_sq)
-    }
-    fn get_pawn_moves(&self, start_sq: Square) -> Vec<Square> {
-        let team = self.get_piece(start_sq).unwrap().team();
-        let mut potential_moves = vec![];
-
-        let start_rank = start_sq.rank() as i32;
-        let start_file = start_sq.file() as i32;
-
-        let unmoved_rank = if team.is_white() { 1 } else { 6 };
-        let last_rank = if team.is_white() { 7 } else { 0 };
-        let move_direction = if team.is_white() { 1 } else { -1 };
-        let single_move_rank = start_rank + move_direction;
-        let double_move_rank = single_move_rank + move_direction;
-
-        if start_rank != last_rank {
-            // in front
-            if self
-                .get_piece(Square::from_i32(single_move_rank, start_file).unwrap())
-                .is_none()
-            {
-                potential_moves.push(Square::from_i32(single_move_rank, start_file).unwrap());
-                // double move
-                if start_rank == unmoved_rank
-                    && self
-                        .get_piece(Square::from_i32(double_move_rank, start_file).unwrap())
-                        .is_none()
-                {
-                    potential_moves.push(Square::from_i32(double_move_rank, start_file).unwrap());
-                }
-            }
-
-            // on the right
-            if start_file != 7 {
-                let right_attack_sq = Square::from_i32(single_move_rank, start_file + 1).unwrap();
-                if self.get_piece(right_attack_sq).is_some() {
-                    potential_moves.push(right_attack_sq);
-                }
-            }
-
-            // on the left
-            if start_file != 0 {
-                let left_attack_sq = Square::from_i32(single_move_rank, start_file - 1).unwrap();
-                if self.get_piece(left_attack_sq).is_some() {
-                    potential_moves.push(left_attack_sq);
-                }
-            }
-        }
-
-        // en passant
-        if self.en_passant_pawn.is_some()
-            && start_sq.rank_diff(self.en_passant_pawn.unwrap()) == 0
-            && start_sq.file_diff(self.en_passant_pawn.unwrap()) == 1
-        {
-            potential_moves.push(Square::new(
-                single_move_rank as usize,
-                self.en_passant_pawn.unwrap().file(),
-            ));
-        }
-
-        potential_moves
-    }
-    fn get_possible_moves(&self, start_sq: Square) -> Vec<Square> {
-        let start_piece = self.get_piece(start_sq).unwrap();
-
-        let mut possible_moves: Vec<Square> = match start_piece.piece_type() {
-            King => self.get_king_moves(start_sq),
-            Queen => self.get_queen_moves(start_sq),
-            Bishop => self.get_bishop_moves(start_sq),
-            Knight => self.get_knight_moves(start_sq),
-            Rook => self.get_rook_moves(start_sq),
-            Pawn => self.get_pawn_moves(start_sq),
-        };
-
-        possible_moves.retain(|target_sq| {
-            let target_piece = self.get_piece(*target_sq);
-            !(target_piece.is_some() && target_piece.unwrap().team() == start_piece.team())
-        });
-
-        possible_moves
-    }
-    fn move_piece_test(&self, start_sq: Square, target_sq: Square) -> Game {
-        let mut test_game = *self;
-        let piece = self.board[start_sq.rank][start_sq.file];
-        test_game.board[start_sq.rank][start_sq.file] = None;
-        test_game.board[target_sq.rank][target_sq.file] = piece;
-        test_game.switch_turn();
-        test_game
-    }
     fn switch_turn(&mut self) {
         match self.turn() {
             Black => self.turn = White,
             White => self.turn = Black,
         }
     }
-    fn find_last_rank_pawn(&self) -> Option<(usize, usize)> {
-        let white_rank_res = self.get_board()[0]
-            .iter()
-            .position(|piece| piece.is_some() && piece.unwrap().is_pawn());
-
-        let black_rank_res = self.get_board()[7]
-            .iter()
-            .position(|piece| piece.is_some() && piece.unwrap().is_pawn());
-
-        if let Some(file) = white_rank_res {
-            return Some((0, file));
-        } else if let Some(file) = black_rank_res {
-            return Some((7, file));
-        }
-
-        None
-    }
-    pub fn reset(&mut self) {
-        *self = Game::init(self.rule_set().to_string());
-    }
-    pub fn init(rule_set: String) -> Game {
-        Game {
+    fn new(rule_set: RuleSet) -> Game {
+        GameDump {
             board: get_default_board(),
             white_king_moved: false,
             white_rook_a_moved: false,
@@ -561,17 +393,46 @@ impl Game {
             black_king_moved: false,
             black_rook_a_moved: false,
             black_rook_h_moved: false,
+            castling_rooks: None,
             en_passant_pawn: None,
             turn: White,
             move_num: 0,
-            rule_set: RuleSet::from(&rule_set).unwrap(),
-        }
+            rule_set,
+        }
+        .try_into()
+        .expect("the starting position is a valid dump")
+    }
+    fn team_in_check(&self, loser_team: Team) -> Result<bool, ChessError> {
+        let loser_king_sq = self.king_square(loser_team)?;
+        Ok(!self
+            .attackers_to(loser_king_sq, loser_team.opponent(), self.occupied())
+            .is_empty())
+    }
+    fn legal_moves(&self, start_sq: Square) -> Result<Vec<Square>, ChessError> {
+        let from = start_sq.index();
+        let mut targets: Vec<Square> = self
+            .generate_legal_moves()?
+            .into_iter()
+            .filter(|mv| mv.from == from)
+            .map(|mv| Square::from_index(self.shown_target(mv)))
+            .collect();
+        // a promotion lands on the same square whichever piece it makes
+        targets.dedup();
+        Ok(targets)
+    }
+    pub fn reset(&mut self) {
+        *self = Game::new(self.rule_set());
     }
-    pub fn dump(&self) -> String {
-        serde_json::to_string(self).unwrap()
+    pub fn init(rule_set: String) -> Result<Game, ChessError> {
+        RuleSet::from(&rule_set).map(Game::new)
     }
-    pub fn from_dump(&mut self, dump: String) {
-        *self = serde_json::from_str(&dump).unwrap();
+    pub fn dump(&self) -> Result<String, ChessError> {
+        serde_json::to_string(self).map_err(|err| ChessError::Serialization(err.to_string()))
+    }
+    pub fn from_dump(&mut self, dump: String) -> Result<(), ChessError> {
+        let dump: GameDump = serde_json::from_str(&dump).map_err(|err| ChessError::InvalidDump(err.to_string()))?;
+        *self = Game::try_from(dump)?;
+        Ok(())
     }
     pub fn is_white_turn(&self) -> bool {
         self.turn() == White
@@ -582,205 +443,108 @@ impl Game {
     pub fn move_num(&self) -> u32 {
         self.move_num
     }
-    pub fn js_board(&self) -> JsValue {
-        serde_wasm_bindgen::to_value(&self.get_board()).unwrap()
+    pub fn js_board(&self) -> Result<JsValue, ChessError> {
+        to_js(&self.get_board())
     }
-    pub fn get_check_coords(&self) -> JsValue {
+    pub fn get_check_coords(&self) -> Result<JsValue, ChessError> {
         let mut king_coords = vec![];
         let mut attacking_pieces = vec![];
 
         for loser_team in [White, Black] {
-            let winner_team = if loser_team.is_white() { Black } else { White };
-
-            let winning_piece_squares = self.find_team_pieces(winner_team);
-            let loser_king_sq = &self.find_king(loser_team);
-            for winner_piece_sq in winning_piece_squares {
-                if self.get_possible_moves(winner_piece_sq).contains(loser_king_sq) {
-                    king_coords.push(loser_king_sq.coords());
-                    attacking_pieces.push(winner_piece_sq.coords());
-                };
+            let loser_king_sq = self.king_square(loser_team)?;
+            for winner_piece_sq in self.attackers_to(loser_king_sq, loser_team.opponent(), self.occupied()) {
+                king_coords.push(Square::from_index(loser_king_sq).coords());
+                attacking_pieces.push(Square::from_index(winner_piece_sq).coords());
             }
         }
 
         king_coords.append(&mut attacking_pieces);
 
-        serde_wasm_bindgen::to_value(&king_coords).unwrap()
+        to_js(&king_coords)
     }
-    pub fn in_check(&self, loser_team_str: &str) -> bool {
-        let loser_team = Team::from(loser_team_str);
-        if loser_team.is_err() {
-            console_log!("invalid team passed to in_checkmate: {:?}", loser_team.unwrap());
-            panic!("invalid piece selected")
-        }
-        let loser_team = loser_team.unwrap();
-        let winner_team = if loser_team.is_white() { Black } else { White };
-
-        let winning_piece_squares = self.find_team_pieces(winner_team);
-        let loser_king_sq = &self.find_king(loser_team);
-        for winner_piece_sq in winning_piece_squares {
-            if self.get_possible_moves(winner_piece_sq).contains(loser_king_sq) {
-                return true;
-            };
-        }
-        false
-    }
-    pub fn get_legal_moves(&self, start_sq_str: &str) -> Vec<String> {
-        // validate input
-        let start_sq = Square::from(start_sq_str);
-        if start_sq.is_err() {
-            console_log!(
-                "invalid coordinates or team passed to get_legal_moves coords: {}",
-                start_sq_str,
-            );
-            panic!("invalid coordinates passed to get_legal_moves");
-        }
-        let start_sq = start_sq.unwrap();
-        let team = self.get_piece(start_sq).unwrap().team();
-
-        // make sure the player owns the piece they are trying to move
-        let start_piece = self.get_piece(start_sq);
-        if start_piece.is_none() {
-            return vec![];
-        }
-        let start_piece = start_piece.unwrap();
-        if start_piece.team() != self.turn() {
-            return vec![];
-        }
-
-        let mut legal_moves = self.get_possible_moves(start_sq);
-
-        legal_moves.retain(|possible_move| {
-            // cannot move into check
-            let test_game = self.move_piece_test(start_sq, *possible_move);
-            if (team.is_white() && test_game.in_check("White")) || (team.is_black() && test_game.in_check("Black")) {
-                return false;
-            }
-
-            // cannot castle from check
-            let piece = self.get_piece(start_sq).unwrap();
-
-            let attempting_castle = piece.is_king() && start_sq.file_diff(possible_move.to_owned()) > 1;
-
-            let in_check = (team.is_white() && self.in_check("White")) || (team.is_black() && self.in_check("Black"));
-
-            if attempting_castle && in_check {
-                return false;
-            }
-
-            if attempting_castle {
-                let middle_sq =
-                    Square::from_usize(start_sq.rank(), (start_sq.file() + possible_move.file()) / 2).unwrap();
-                let castle_test_game = self.move_piece_test(start_sq, middle_sq);
-
-                let castling_thru_check = self.turn().is_white() && castle_test_game.in_check("White")
-                    || self.turn().is_black() && castle_test_game.in_check("Black");
-                if castling_thru_check {
-                    return false;
-                }
-            }
-            true
-        });
-
-        legal_moves.iter().map(|possible_move| possible_move.coords()).collect()
+    pub fn in_check(&self, loser_team_str: &str) -> Result<bool, ChessError> {
+        self.team_in_check(Team::from(loser_team_str)?)
     }
-    pub fn has_last_rank_pawn(&self) -> bool {
-        self.find_last_rank_pawn().is_some()
+    pub fn get_legal_moves(&self, start_sq_str: &str) -> Result<Vec<String>, ChessError> {
+        let legal_moves = self.legal_moves(Square::from(start_sq_str)?)?;
+        Ok(legal_moves.iter().map(|possible_move| possible_move.coords()).collect())
     }
-    pub fn replace_last_rank_pawn(&mut self, piece_type_str: &str) {
-        let replacement_piece = PieceType::from(piece_type_str);
-        if replacement_piece.is_err() {
-            console_log!(
-                "invalid piece or team passed to replace_last_rank_pawn: {:?}",
-                replacement_piece.unwrap(),
-            );
-            panic!("invalid piece selected")
-        }
-        let replacement_piece = replacement_piece.unwrap();
-
-        let (pawn_rank, pawn_file) = self.find_last_rank_pawn().unwrap();
-
-        let team = if pawn_rank == 0 { Black } else { White };
-
-        self.board[pawn_rank][pawn_file] = Some(Piece::new(replacement_piece, team));
+    /// Whether moving from `start_sq_str` to `target_sq_str` is legal and needs a piece to
+    /// promote to, so the UI only asks for one when it'll be used.
+    pub fn is_promotion(&self, start_sq_str: &str, target_sq_str: &str) -> Result<bool, ChessError> {
+        let start_sq = Square::from(start_sq_str)?;
+        let target_sq = Square::from(target_sq_str)?;
+        Ok(self.pieces(Pawn).contains(start_sq.index())
+            && matches!(target_sq.rank(), 0 | 7)
+            && self.legal_moves(start_sq)?.contains(&target_sq))
     }
-    pub fn in_checkmate(&self, loser_team_str: &str) -> bool {
-        let loser_team = Team::from(loser_team_str);
-        if loser_team.is_err() {
-            console_log!("invalid team passed to in_checkmate: {:?}", loser_team.unwrap());
-            panic!("invalid piece selected")
-        }
-        let loser_team = loser_team.unwrap();
-        let loser_piece_squares = self.find_team_pieces(loser_team);
-        let mut checkmate = true;
+    pub fn in_checkmate(&self, loser_team_str: &str) -> Result<bool, ChessError> {
+        let loser_team = Team::from(loser_team_str)?;
         let mut test_game = *self;
-        test_game.turn = loser_team;
-        for loser_piece_sq in loser_piece_squares {
-            if !test_game.get_legal_moves(&loser_piece_sq.coords()).is_empty() {
-                checkmate = false;
-                return checkmate;
-            }
-        }
-        checkmate
+        if test_game.turn != loser_team {
+            // an en passant capture is only ever open to the side to move
+            test_game.turn = loser_team;
+            test_game.en_passant_pawn = None;
+        }
+        Ok(test_game.generate_legal_moves()?.is_empty())
+    }
+    /// Plays a move, promoting to `promotion` if it takes a pawn to the last rank. Promotion moves
+    /// without a piece are rejected and nothing changes.
+    pub fn move_piece(
+        &mut self,
+        start_sq_str: &str,
+        target_sq_str: &str,
+        promotion: Option<String>,
+    ) -> Result<JsValue, ChessError> {
+        let last_moved_coords = self.execute_move(start_sq_str, target_sq_str, promotion.as_deref())?;
+        to_js(&last_moved_coords)
+    }
+    fn execute_move(
+        &mut self,
+        start_sq_str: &str,
+        target_sq_str: &str,
+        promotion: Option<&str>,
+    ) -> Result<Vec<Vec<String>>, ChessError> {
+        let start_sq = Square::from(start_sq_str)?;
+        let target_sq = Square::from(target_sq_str)?;
+        let promotion = promotion.map(PieceType::promotion).transpose()?;
+        let mut mv = self.move_from_squares(start_sq.index(), target_sq.index());
+        if self.is_promotion(start_sq_str, target_sq_str)? {
+            mv.promotion = Some(promotion.ok_or(ChessError::MissingPromotion)?);
+        }
+        if !self.generate_legal_moves()?.contains(&mv) {
+            return Err(ChessError::IllegalMove {
+                from: start_sq_str.to_string(),
+                to: target_sq_str.to_string(),
+            });
+        }
+
+        let mut last_moved_coords = vec![vec![start_sq.coords(), target_sq.coords()]];
+        if self.is_castling(mv) {
+            let (king_to, rook_to) = castling_targets(mv.from, mv.to);
+            last_moved_coords = vec![
+                vec![start_sq.coords(), Square::from_index(king_to).coords()],
+                vec![Square::from_index(mv.to).coords(), Square::from_index(rook_to).coords()],
+            ];
+        }
+        self.play(mv);
+
+        Ok(last_moved_coords)
     }
-    pub fn move_piece(&mut self, start_sq_str: &str, target_sq_str: &str) -> Result<JsValue, String> {
-        let mut last_moved_coords: Vec<Vec<String>> = vec![];
-        if !self.get_legal_moves(start_sq_str).contains(&target_sq_str.to_string()) {
-            return Err("Error: Invalid Move".to_string());
-        }
-
-        let start_sq = Square::from(start_sq_str).unwrap();
-        let target_sq = Square::from(target_sq_str).unwrap();
-        last_moved_coords.push(vec![start_sq.coords(), target_sq.coords()]);
-
-        let piece = self.board[start_sq.rank()][start_sq.file()].unwrap();
-
-        // castle
-        if piece.is_king() && start_sq.file_diff(target_sq) > 1 {
-            let rook_start_file;
-            let rook_end_file;
-            if target_sq.file() == 6 {
-                rook_start_file = 7;
-                rook_end_file = 5;
-            } else {
-                rook_start_file = 0;
-                rook_end_file = 3;
-            }
-            last_moved_coords.push(vec![
-                Square::from_usize(target_sq.rank(), rook_start_file).unwrap().coords(),
-                Square::from_usize(target_sq.rank(), rook_end_file).unwrap().coords(),
-            ]);
-            let rook = self.board[target_sq.rank()][rook_start_file];
-            self.board[target_sq.rank()][rook_start_file] = None;
-            self.board[target_sq.rank()][rook_end_file] = rook
-        }
-
-        // en passant
-        if piece.is_pawn() && self.get_piece(target_sq).is_none() && start_sq.file() != target_sq.file() {
-            self.board[start_sq.rank()][target_sq.file()] = None;
-        }
-        // execute move
-        self.board[start_sq.rank()][start_sq.file()] = None;
-        self.board[target_sq.rank()][target_sq.file()] = Some(piece);
-
-        // update moved pieces & en passant
-        match start_sq.coords().as_str() {
-            "e1" => self.white_king_moved = true,
-            "a1" => self.white_rook_a_moved = true,
-            "h1" => self.white_rook_h_moved = true,
-            "e8" => self.black_king_moved = true,
-            "a8" => self.black_rook_a_moved = true,
-            "h8" => self.black_rook_h_moved = true,
-            _ => {}
-        }
-        if piece.is_pawn() && start_sq.rank_diff(target_sq) == 2 {
-            self.en_passant_pawn = Some(target_sq);
-        } else {
-            self.en_passant_pawn = None;
-        }
-
-        self.switch_turn();
-        self.move_num += 1;
+}
 
-        Ok(serde_wasm_bindgen::to_value(&last_moved_coords).unwrap())
+impl Game {
+    /// `move_piece` for callers outside the browser, e.g. the server validating relayed moves.
+    pub fn try_move(
+        &mut self,
+        start_sq_str: &str,
+        target_sq_str: &str,
+        promotion: Option<&str>,
+    ) -> Result<Vec<Vec<String>>, ChessError> {
+        self.execute_move(start_sq_str, target_sq_str, promotion)
     }
 }
+
+fn to_js<T: Serialize>(value: &T) -> Result<JsValue, ChessError> {
+    serde_wasm_bindgen::to_value(value).map_err(|err| ChessError::Serialization(err.to_string()))
+}
@@ -0,0 +1,294 @@
+//! Legal move generation. Instead of trying each pseudo-legal move and testing for check, moves
+//! are masked up front: in check, pieces may only capture the checker or block it, and pinned
+//! pieces may only move along their pin. Only king steps and en passant are tested directly.
+
+use crate::{
+    attacks,
+    bitboard::Bitboard,
+    castling::{castles_two_squares, castling_targets},
+    ChessError, Game, Piece,
+    PieceType::{self, *},
+    Square,
+    Team::{self, *},
+};
+
+/// A move between two squares, indexed a1 = 0 to h8 = 63. Castling is the king taking its own
+/// rook, which stays unambiguous in Chess960 where the king may castle onto its own square, and a
+/// pawn reaching the last rank carries the piece it promotes to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Move {
+    pub(crate) from: usize,
+    pub(crate) to: usize,
+    pub(crate) promotion: Option<PieceType>,
+}
+
+impl Move {
+    pub(crate) fn new(from: usize, to: usize) -> Move {
+        Move {
+            from,
+            to,
+            promotion: None,
+        }
+    }
+}
+
+impl Game {
+    pub(crate) fn pieces(&self, piece_type: PieceType) -> Bitboard {
+        self.pieces[piece_type as usize]
+    }
+    pub(crate) fn team_pieces(&self, team: Team) -> Bitboard {
+        self.teams[team as usize]
+    }
+    fn team_pieces_of(&self, team: Team, piece_type: PieceType) -> Bitboard {
+        self.team_pieces(team) & self.pieces(piece_type)
+    }
+    pub(crate) fn occupied(&self) -> Bitboard {
+        self.teams[0] | self.teams[1]
+    }
+    pub(crate) fn piece_at(&self, sq: usize) -> Option<Piece> {
+        let team = [White, Black]
+            .into_iter()
+            .find(|team| self.team_pieces(*team).contains(sq))?;
+        let piece_type = PieceType::ALL
+            .into_iter()
+            .find(|piece_type| self.pieces(*piece_type).contains(sq))?;
+        Some(Piece::new(piece_type, team))
+    }
+    pub(crate) fn put(&mut self, sq: usize, piece: Piece) {
+        self.remove(sq);
+        self.pieces[piece.piece_type() as usize] |= Bitboard::square(sq);
+        self.teams[piece.team() as usize] |= Bitboard::square(sq);
+    }
+    pub(crate) fn remove(&mut self, sq: usize) {
+        let keep = !Bitboard::square(sq);
+        for bitboard in self.pieces.iter_mut().chain(self.teams.iter_mut()) {
+            *bitboard &= keep;
+        }
+    }
+    pub(crate) fn king_square(&self, team: Team) -> Result<usize, ChessError> {
+        self.team_pieces_of(team, King)
+            .first()
+            .ok_or_else(|| ChessError::MissingKing(team.to_string()))
+    }
+
+    /// `by`'s pieces attacking `sq`, as if the board held `occupied`.
+    pub(crate) fn attackers_to(&self, sq: usize, by: Team, occupied: Bitboard) -> Bitboard {
+        let diagonal = self.pieces(Bishop) | self.pieces(Queen);
+        let straight = self.pieces(Rook) | self.pieces(Queen);
+        let attackers = (attacks::pawn(by.opponent(), sq) & self.pieces(Pawn))
+            | (attacks::knight(sq) & self.pieces(Knight))
+            | (attacks::king(sq) & self.pieces(King))
+            | (attacks::bishop(sq, occupied) & diagonal)
+            | (attacks::rook(sq, occupied) & straight);
+        attackers & self.team_pieces(by)
+    }
+
+    /// Pieces of `team` that are the only thing standing between their king and an enemy slider.
+    fn pinned(&self, king_sq: usize, team: Team) -> Bitboard {
+        let them = team.opponent();
+        let snipers = (attacks::bishop(king_sq, Bitboard::EMPTY)
+            & (self.team_pieces_of(them, Bishop) | self.team_pieces_of(them, Queen)))
+            | (attacks::rook(king_sq, Bitboard::EMPTY)
+                & (self.team_pieces_of(them, Rook) | self.team_pieces_of(them, Queen)));
+
+        let mut pinned = Bitboard::EMPTY;
+        for sniper in snipers {
+            let blockers = attacks::between(king_sq, sniper) & self.occupied();
+            if !blockers.more_than_one() {
+                pinned |= blockers & self.team_pieces(team);
+            }
+        }
+        pinned
+    }
+
+    fn pawn_targets(&self, from: usize, team: Team) -> Bitboard {
+        let occupied = self.occupied();
+        let (unmoved_rank, forward) = if team.is_white() { (1, 8) } else { (6, -8) };
+        let mut targets = attacks::pawn(team, from) & self.team_pieces(team.opponent());
+
+        let single = from as i32 + forward;
+        if (0..64).contains(&single) && !occupied.contains(single as usize) {
+            targets |= Bitboard::square(single as usize);
+            let double = single + forward;
+            if from / 8 == unmoved_rank && !occupied.contains(double as usize) {
+                targets |= Bitboard::square(double as usize);
+            }
+        }
+        targets
+    }
+
+    /// Every legal move for the side to move.
+    pub(crate) fn generate_legal_moves(&self) -> Result<Vec<Move>, ChessError> {
+        let us = self.turn();
+        let them = us.opponent();
+        let ours = self.team_pieces(us);
+        let occupied = self.occupied();
+        let king_sq = self.king_square(us)?;
+        let checkers = self.attackers_to(king_sq, them, occupied);
+        let mut moves = Vec::with_capacity(64);
+
+        // the king is taken off the board so it can't hide behind itself from a slider
+        let without_king = occupied ^ Bitboard::square(king_sq);
+        let king_safe = |sq: usize| self.attackers_to(sq, them, without_king).is_empty();
+        for to in attacks::king(king_sq) & !ours {
+            if king_safe(to) {
+                moves.push(Move::new(king_sq, to));
+            }
+        }
+        if checkers.more_than_one() {
+            return Ok(moves);
+        }
+
+        let check_mask = match checkers.first() {
+            Some(checker) => attacks::between(king_sq, checker) | checkers,
+            None => Bitboard::ALL,
+        };
+        let pinned = self.pinned(king_sq, us);
+        for from in ours & !self.pieces(King) {
+            let Some(piece) = self.piece_at(from) else {
+                continue;
+            };
+            let mut targets = match piece.piece_type() {
+                Pawn => self.pawn_targets(from, us),
+                Knight => attacks::knight(from),
+                Bishop => attacks::bishop(from, occupied),
+                Rook => attacks::rook(from, occupied),
+                Queen => attacks::bishop(from, occupied) | attacks::rook(from, occupied),
+                King => Bitboard::EMPTY,
+            } & !ours
+                & check_mask;
+            if pinned.contains(from) {
+                targets &= attacks::line(king_sq, from);
+            }
+            if piece.is_pawn() {
+                push_pawn_moves(&mut moves, from, targets);
+            } else {
+                moves.extend(targets.map(|to| Move::new(from, to)));
+            }
+        }
+
+        // en passant only ever takes an enemy pawn that has just pushed two squares, and it moves
+        // two pawns off their squares at once, so it's played out on the occupancy and checked
+        if let Some(captured) = self.en_passant_pawn.map(|pawn| pawn.index()) {
+            let (pushed_to_rank, to) = if us.is_white() {
+                (4, captured + 8)
+            } else {
+                (3, captured.wrapping_sub(8))
+            };
+            if captured / 8 == pushed_to_rank
+                && self.team_pieces_of(them, Pawn).contains(captured)
+                && !occupied.contains(to)
+            {
+                for from in attacks::pawn(them, to) & self.team_pieces_of(us, Pawn) {
+                    let after = occupied ^ Bitboard::square(from) ^ Bitboard::square(captured) | Bitboard::square(to);
+                    let attackers = self.attackers_to(king_sq, them, after) & !Bitboard::square(captured);
+                    if attackers.is_empty() {
+                        moves.push(Move::new(from, to));
+                    }
+                }
+            }
+        }
+
+        // castling, out of check, with nothing but the king and rook on either's path and the king's
+        // path safe once both have left their squares
+        if checkers.is_empty() {
+            for rook_sq in self.castling.rooks(us) {
+                let (king_to, rook_to) = castling_targets(king_sq, rook_sq);
+                let king_path = attacks::between(king_sq, king_to) | Bitboard::square(king_to);
+                let rook_path = attacks::between(rook_sq, rook_to) | Bitboard::square(rook_to);
+                let others = occupied ^ Bitboard::square(king_sq) ^ Bitboard::square(rook_sq);
+                if self.team_pieces_of(us, Rook).contains(rook_sq)
+                    && ((king_path | rook_path) & others).is_empty()
+                    && king_path
+                        .into_iter()
+                        .all(|sq| self.attackers_to(sq, them, others).is_empty())
+                {
+                    moves.push(Move::new(king_sq, rook_sq));
+                }
+            }
+        }
+
+        Ok(moves)
+    }
+
+    /// Whether `mv` is castling, which is encoded as the king taking its own rook.
+    pub(crate) fn is_castling(&self, mv: Move) -> bool {
+        self.pieces(King).contains(mv.from) && self.team_pieces_of(self.turn(), Rook).contains(mv.to)
+    }
+
+    /// Reads a move the way the board shows it, where a king and rook on their standard squares
+    /// castle by the king moving two squares. From any other squares, as in Chess960, the king
+    /// moves onto the rook.
+    pub(crate) fn move_from_squares(&self, from: usize, to: usize) -> Move {
+        if self.pieces(King).contains(from) && (from % 8).abs_diff(to % 8) == 2 {
+            if let Some(rook_sq) = self.castling.rook(self.turn(), to > from) {
+                if castles_two_squares(from, rook_sq) {
+                    return Move::new(from, rook_sq);
+                }
+            }
+        }
+        Move::new(from, to)
+    }
+
+    /// Where the board shows `mv` landing, the reverse of `move_from_squares`.
+    pub(crate) fn shown_target(&self, mv: Move) -> usize {
+        if self.is_castling(mv) && castles_two_squares(mv.from, mv.to) {
+            castling_targets(mv.from, mv.to).0
+        } else {
+            mv.to
+        }
+    }
+
+    /// Applies a move from `generate_legal_moves`, including the rook's half of castling and the
+    /// pawn taken en passant.
+    pub(crate) fn play(&mut self, mv: Move) {
+        let Some(piece) = self.piece_at(mv.from) else {
+            return;
+        };
+        let file_diff = (mv.from % 8).abs_diff(mv.to % 8);
+
+        if self.is_castling(mv) {
+            let (king_to, rook_to) = castling_targets(mv.from, mv.to);
+            self.remove(mv.from);
+            self.remove(mv.to);
+            self.put(king_to, piece);
+            self.put(rook_to, Piece::new(Rook, piece.team()));
+        } else {
+            if piece.is_pawn() && file_diff == 1 && !self.occupied().contains(mv.to) {
+                self.remove(mv.from / 8 * 8 + mv.to % 8);
+            }
+            self.remove(mv.from);
+            let promoted = mv.promotion.map(|piece_type| Piece::new(piece_type, piece.team()));
+            self.put(mv.to, promoted.unwrap_or(piece));
+        }
+
+        if piece.is_king() {
+            self.castling.revoke(piece.team());
+        }
+        self.castling.revoke_rook(mv.from);
+        self.castling.revoke_rook(mv.to);
+        self.en_passant_pawn = if piece.is_pawn() && (mv.from / 8).abs_diff(mv.to / 8) == 2 {
+            Some(Square::from_index(mv.to))
+        } else {
+            None
+        };
+
+        self.switch_turn();
+        self.move_num += 1;
+    }
+}
+
+/// Pawn moves onto the last rank come once for each piece the pawn can promote to.
+fn push_pawn_moves(moves: &mut Vec<Move>, from: usize, targets: Bitboard) {
+    for to in targets {
+        if matches!(to / 8, 0 | 7) {
+            moves.extend(PieceType::PROMOTIONS.map(|piece_type| Move {
+                from,
+                to,
+                promotion: Some(piece_type),
+            }));
+        } else {
+            moves.push(Move::new(from, to));
+        }
+    }
+}
@@ -0,0 +1,61 @@
+//! Move path enumeration for checking move generation against known node counts.
+
+use wasm_bindgen::prelude::*;
+
+use crate::{movegen::Move, to_js, ChessError, Game, PieceType::*, Square};
+
+#[wasm_bindgen]
+impl Game {
+    /// The number of leaf positions `depth` moves from here.
+    pub fn perft(&self, depth: u32) -> Result<u64, ChessError> {
+        if depth == 0 {
+            return Ok(1);
+        }
+        let moves = self.generate_legal_moves()?;
+        if depth == 1 {
+            return Ok(moves.len() as u64);
+        }
+        let mut nodes = 0;
+        for mv in moves {
+            nodes += self.after(mv).perft(depth - 1)?;
+        }
+        Ok(nodes)
+    }
+    /// `divide` as an array of `[move, nodes]` pairs.
+    pub fn js_divide(&self, depth: u32) -> Result<JsValue, ChessError> {
+        to_js(&self.divide(depth)?)
+    }
+}
+
+impl Game {
+    /// `perft` split by first move, named like `e2e4` or `e7e8q`, for narrowing down which move
+    /// disagrees with another engine.
+    pub fn divide(&self, depth: u32) -> Result<Vec<(String, u64)>, ChessError> {
+        let mut counts = vec![];
+        for mv in self.generate_legal_moves()? {
+            let nodes = self.after(mv).perft(depth.saturating_sub(1))?;
+            counts.push((self.move_name(mv), nodes));
+        }
+        Ok(counts)
+    }
+    fn after(&self, mv: Move) -> Game {
+        let mut game = *self;
+        game.play(mv);
+        game
+    }
+    fn move_name(&self, mv: Move) -> String {
+        let promotion = match mv.promotion {
+            Some(Queen) => "q",
+            Some(Rook) => "r",
+            Some(Bishop) => "b",
+            Some(Knight) => "n",
+            _ => "",
+        };
+        format!(
+            "{}{}{}",
+            Square::from_index(mv.from),
+            Square::from_index(self.shown_target(mv)),
+            promotion
+        )
+    }
+}
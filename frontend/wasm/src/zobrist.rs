@@ -0,0 +1,74 @@
+//! Zobrist hashing: a fixed random key per piece on each square, per castling rook and per en
+//! passant file, xored together so that equal positions hash equally.
+
+use crate::{Game, PieceType, Team::*};
+
+struct Keys {
+    pieces: [[[u64; 64]; 6]; 2],
+    castling: [[u64; 64]; 2],
+    en_passant: [u64; 8],
+    black_to_move: u64,
+}
+
+static KEYS: Keys = keys();
+
+/// Keys from a fixed xorshift sequence, so hashes match between builds.
+const fn keys() -> Keys {
+    let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+    let mut keys = Keys {
+        pieces: [[[0; 64]; 6]; 2],
+        castling: [[0; 64]; 2],
+        en_passant: [0; 8],
+        black_to_move: 0,
+    };
+    let mut i = 0;
+    while i < 2 * 6 * 64 {
+        state = xorshift(state);
+        keys.pieces[i / 384][i / 64 % 6][i % 64] = state;
+        i += 1;
+    }
+    i = 0;
+    while i < 2 * 64 {
+        state = xorshift(state);
+        keys.castling[i / 64][i % 64] = state;
+        i += 1;
+    }
+    i = 0;
+    while i < 8 {
+        state = xorshift(state);
+        keys.en_passant[i] = state;
+        i += 1;
+    }
+    keys.black_to_move = xorshift(state);
+    keys
+}
+
+const fn xorshift(mut state: u64) -> u64 {
+    state ^= state << 13;
+    state ^= state >> 7;
+    state ^= state << 17;
+    state
+}
+
+impl Game {
+    pub(crate) fn hash(&self) -> u64 {
+        let mut hash = 0;
+        for team in [White, Black] {
+            for piece_type in PieceType::ALL {
+                for sq in self.team_pieces(team) & self.pieces(piece_type) {
+                    hash ^= KEYS.pieces[team as usize][piece_type as usize][sq];
+                }
+            }
+            for rook_sq in self.castling.rooks(team) {
+                hash ^= KEYS.castling[team as usize][rook_sq];
+            }
+        }
+        if let Some(pawn) = self.en_passant_pawn {
+            hash ^= KEYS.en_passant[pawn.file()];
+        }
+        if self.turn() == Black {
+            hash ^= KEYS.black_to_move;
+        }
+        hash
+    }
+}
@@ -0,0 +1,154 @@
+use chess::Game;
+
+fn game(fen: &str) -> Game {
+    Game::from_fen(fen).unwrap()
+}
+
+fn play(game: &mut Game, moves: &[(&str, &str)]) {
+    for (from, to) in moves {
+        game.try_move(from, to, None).unwrap();
+    }
+}
+
+fn king_moves(game: &Game, king_sq: &str) -> Vec<String> {
+    game.get_legal_moves(king_sq).unwrap()
+}
+
+#[test]
+fn captured_rook_revokes_castling() {
+    let mut game = game("r3k2r/8/8/8/8/8/6b1/R3K2R b KQkq - 0 1");
+    play(&mut game, &[("g2", "h1")]);
+    let moves = king_moves(&game, "e1");
+    assert!(!moves.contains(&"g1".to_string()));
+    assert!(moves.contains(&"c1".to_string()));
+}
+
+#[test]
+fn queenside_castling_needs_the_b_file_empty() {
+    let game = game("r3k2r/8/8/8/8/8/8/RN2K2R w KQkq - 0 1");
+    let moves = king_moves(&game, "e1");
+    assert!(!moves.contains(&"c1".to_string()));
+    assert!(moves.contains(&"g1".to_string()));
+}
+
+#[test]
+fn rook_leaving_and_returning_revokes_only_its_side() {
+    let mut game = game("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+    play(&mut game, &[("h1", "h2"), ("a8", "a7"), ("h2", "h1"), ("a7", "a8")]);
+    let moves = king_moves(&game, "e1");
+    assert!(!moves.contains(&"g1".to_string()));
+    assert!(moves.contains(&"c1".to_string()));
+}
+
+#[test]
+fn king_leaving_and_returning_revokes_both_sides() {
+    let mut game = game("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+    play(&mut game, &[("e1", "e2"), ("a8", "b8"), ("e2", "e1"), ("b8", "a8")]);
+    let moves = king_moves(&game, "e1");
+    assert!(!moves.contains(&"g1".to_string()));
+    assert!(!moves.contains(&"c1".to_string()));
+}
+
+#[test]
+fn king_may_not_pass_through_an_attacked_square() {
+    let game = game("r3k2r/8/8/8/8/8/5r2/R3K2R w KQkq - 0 1");
+    let moves = king_moves(&game, "e1");
+    assert!(!moves.contains(&"g1".to_string()));
+    assert!(moves.contains(&"c1".to_string()));
+}
+
+#[test]
+fn rook_passing_an_attacked_square_is_fine() {
+    let game = game("r3k2r/8/8/8/8/8/1r6/R3K2R w KQkq - 0 1");
+    assert!(king_moves(&game, "e1").contains(&"c1".to_string()));
+}
+
+#[test]
+fn castling_moves_the_rook() {
+    let mut game = game("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+    let coords = game.try_move("e1", "c1", None).unwrap();
+    assert_eq!(coords, [["e1", "c1"], ["a1", "d1"]]);
+    let coords = game.try_move("e8", "g8", None).unwrap();
+    assert_eq!(coords, [["e8", "g8"], ["h8", "f8"]]);
+}
+
+#[test]
+fn chess960_castles_by_moving_the_king_onto_the_rook() {
+    let mut game = game("4k3/8/8/8/8/8/8/1R4KR w HB - 0 1");
+    let moves = king_moves(&game, "g1");
+    assert!(moves.contains(&"h1".to_string()));
+    assert!(moves.contains(&"b1".to_string()));
+
+    // the king is already on g1, so only the rook moves
+    let mut kingside = game;
+    let coords = kingside.try_move("g1", "h1", None).unwrap();
+    assert_eq!(coords, [["g1", "g1"], ["h1", "f1"]]);
+    let coords = game.try_move("g1", "b1", None).unwrap();
+    assert_eq!(coords, [["g1", "c1"], ["b1", "d1"]]);
+}
+
+#[test]
+fn chess960_castling_needs_both_paths_clear() {
+    // the king's path to c1 is clear, but the knight on b1 is in the rook's way
+    let game = game("4k3/8/8/8/8/8/8/RN3K2 w A - 0 1");
+    assert!(!king_moves(&game, "f1").contains(&"a1".to_string()));
+}
+
+#[test]
+fn old_dumps_without_rights_ignore_captured_rooks() {
+    let game = game("r3k2r/8/8/8/8/8/8/R3K3 w Qkq - 0 1");
+    let mut dump: serde_json::Value = serde_json::from_str(&game.dump().unwrap()).unwrap();
+    let dump = dump.as_object_mut().unwrap();
+    dump.remove("castling_rooks");
+    dump.insert("white_rook_h_moved".to_string(), false.into());
+
+    let mut restored = Game::init("standard".to_string()).unwrap();
+    restored.from_dump(serde_json::to_string(dump).unwrap()).unwrap();
+    let moves = king_moves(&restored, "e1");
+    assert!(!moves.contains(&"g1".to_string()));
+    assert!(moves.contains(&"c1".to_string()));
+}
+
+#[test]
+fn shuffled_games_from_the_standard_squares_castle_two_squares() {
+    let mut game = Game::init("shuffled".to_string()).unwrap();
+    play(
+        &mut game,
+        &[
+            ("e2", "e4"),
+            ("e7", "e5"),
+            ("g1", "f3"),
+            ("g8", "f6"),
+            ("f1", "c4"),
+            ("f8", "c5"),
+        ],
+    );
+    let moves = king_moves(&game, "e1");
+    assert!(moves.contains(&"g1".to_string()));
+    assert!(!moves.contains(&"h1".to_string()));
+    let coords = game.try_move("e1", "g1", None).unwrap();
+    assert_eq!(coords, [["e1", "g1"], ["h1", "f1"]]);
+}
+
+#[test]
+fn dumps_with_bad_castling_rooks_are_rejected() {
+    let dump = game("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").dump().unwrap();
+    let with_rooks = |rooks: serde_json::Value| {
+        let mut dump: serde_json::Value = serde_json::from_str(&dump).unwrap();
+        dump["castling_rooks"] = rooks;
+        let mut restored = Game::init("standard".to_string()).unwrap();
+        restored.from_dump(dump.to_string())
+    };
+
+    assert!(with_rooks(serde_json::json!([[0, 7], [56, 63]])).is_ok());
+    // off the board, the opponent's rook, off the back rank, and on the wrong side of the king
+    for rooks in [
+        serde_json::json!([[0, 7], [56, 200]]),
+        serde_json::json!([[56, 7], [null, null]]),
+        serde_json::json!([[0, 15], [null, null]]),
+        serde_json::json!([[7, 0], [null, null]]),
+    ] {
+        let err = with_rooks(rooks.clone()).unwrap_err();
+        assert_eq!(err.code(), "invalid_dump", "{}", rooks);
+    }
+}
@@ -0,0 +1,63 @@
+use std::collections::HashSet;
+
+use chess::{ChessError, Difficulty, Game};
+
+const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+#[test]
+fn levels_run_from_one_to_ten() {
+    assert_eq!(Difficulty::level(0), Err(ChessError::InvalidLevel(0)));
+    assert_eq!(Difficulty::level(11), Err(ChessError::InvalidLevel(11)));
+    let mut previous = Difficulty::level(1).unwrap();
+    for level in 2..=10 {
+        let difficulty = Difficulty::level(level).unwrap();
+        assert!(difficulty.depth >= previous.depth && difficulty.time_ms > previous.time_ms);
+        assert!(difficulty.margin <= previous.margin && difficulty.blunder_chance <= previous.blunder_chance);
+        previous = difficulty;
+    }
+}
+
+#[test]
+fn same_seed_gives_the_same_move() {
+    let game = Game::from_fen(START).unwrap();
+    for level in [1, 4] {
+        assert_eq!(game.bot_search(level, 7).unwrap(), game.bot_search(level, 7).unwrap());
+    }
+}
+
+#[test]
+fn weak_levels_vary_their_moves() {
+    let game = Game::from_fen(START).unwrap();
+    let moves: HashSet<_> = (0..20)
+        .map(|seed| game.bot_search(1, seed).unwrap().unwrap())
+        .map(|result| (result.from, result.to))
+        .collect();
+    assert!(moves.len() > 3, "{:?}", moves);
+}
+
+#[test]
+fn strong_levels_find_mate() {
+    for level in [9, 10] {
+        let game = Game::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let result = game.bot_search(level, 1).unwrap().unwrap();
+        assert_eq!((result.from.as_str(), result.to.as_str()), ("a1", "a8"));
+    }
+}
+
+#[test]
+fn every_level_plays_legal_moves() {
+    let mut game = Game::from_fen(START).unwrap();
+    // the top levels are left out as they take their full thinking time on every move
+    for (seed, level) in (1..=8).chain(1..=4).enumerate() {
+        let result = game.bot_search(level, seed as u64).unwrap().unwrap();
+        game.try_move(&result.from, &result.to, result.promotion.as_deref())
+            .unwrap();
+    }
+    assert_eq!(game.move_num(), 12);
+}
+
+#[test]
+fn no_move_when_checkmated() {
+    let game = Game::from_fen("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1").unwrap();
+    assert_eq!(game.bot_search(3, 0).unwrap(), None);
+}
@@ -0,0 +1,89 @@
+use chess::Game;
+
+fn moves(game: &Game, sq: &str) -> Vec<String> {
+    game.get_legal_moves(sq).unwrap()
+}
+
+#[test]
+fn only_straight_after_the_double_push() {
+    let mut game = Game::init("standard".to_string()).unwrap();
+    for (from, to) in [("e2", "e4"), ("a7", "a6"), ("e4", "e5"), ("d7", "d5")] {
+        game.try_move(from, to, None).unwrap();
+    }
+    assert!(moves(&game, "e5").contains(&"d6".to_string()));
+
+    game.try_move("h2", "h3", None).unwrap();
+    game.try_move("a6", "a5", None).unwrap();
+    assert!(!moves(&game, "e5").contains(&"d6".to_string()));
+}
+
+#[test]
+fn capture_removes_the_passed_pawn() {
+    let mut game = Game::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+    let coords = game.try_move("e5", "d6", None).unwrap();
+    assert_eq!(coords, [["e5", "d6"]]);
+    // with the d5 pawn gone black only has king moves, and e7 is covered by the new d6 pawn
+    assert_eq!(game.perft(1).unwrap(), 4);
+}
+
+#[test]
+fn not_after_a_single_step_or_from_the_wrong_rank() {
+    let mut game = Game::init("standard".to_string()).unwrap();
+    for (from, to) in [("e2", "e4"), ("d7", "d6"), ("e4", "e5"), ("d6", "d5")] {
+        game.try_move(from, to, None).unwrap();
+    }
+    assert_eq!(moves(&game, "e5"), ["e6"]);
+    assert!(Game::from_fen("4k3/8/8/3PP3/8/8/8/4K3 w - d6 0 1").is_err());
+}
+
+#[test]
+fn not_when_both_pawns_leaving_the_rank_exposes_the_king() {
+    let game = Game::from_fen("8/8/8/K2pP2r/8/8/8/4k3 w - d6 0 1").unwrap();
+    assert_eq!(moves(&game, "e5"), ["e6"]);
+}
+
+#[test]
+fn not_when_the_passed_pawn_was_blocking_a_diagonal() {
+    let game = Game::from_fen("8/6k1/8/8/3Pp3/8/8/B6K b - d3 0 1").unwrap();
+    assert_eq!(moves(&game, "e4"), ["e3"]);
+}
+
+#[test]
+fn not_by_a_pinned_pawn_leaving_its_pin() {
+    // the d4 pawn is pinned to the king on g7, and e3 is off the a1-h8 diagonal
+    let game = Game::from_fen("8/6k1/8/8/3pP3/8/8/B3K3 b - e3 0 1").unwrap();
+    assert!(moves(&game, "d4").is_empty());
+}
+
+#[test]
+fn capturing_the_checking_pawn_en_passant_is_legal() {
+    let game = Game::from_fen("8/8/8/4k3/3Pp3/8/8/4K3 b - d3 0 1").unwrap();
+    assert!(game.in_check("Black").unwrap());
+    assert!(moves(&game, "e4").contains(&"d3".to_string()));
+}
+
+#[test]
+fn dumps_with_a_bad_en_passant_pawn_are_rejected() {
+    let dump = Game::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1")
+        .unwrap()
+        .dump()
+        .unwrap();
+    let with_pawn = |pawn: serde_json::Value| {
+        let mut dump: serde_json::Value = serde_json::from_str(&dump).unwrap();
+        dump["en_passant_pawn"] = pawn;
+        let mut restored = Game::init("standard".to_string()).unwrap();
+        restored.from_dump(dump.to_string())
+    };
+
+    assert!(with_pawn(serde_json::json!({ "rank": 4, "file": 3 })).is_ok());
+    // off the board, white's own pawn, and an empty square on the right rank
+    for pawn in [
+        serde_json::json!({ "rank": 9, "file": 3 }),
+        serde_json::json!({ "rank": 4, "file": 12 }),
+        serde_json::json!({ "rank": 4, "file": 4 }),
+        serde_json::json!({ "rank": 4, "file": 0 }),
+    ] {
+        let err = with_pawn(pawn.clone()).unwrap_err();
+        assert_eq!(err.code(), "invalid_dump", "{}", pawn);
+    }
+}
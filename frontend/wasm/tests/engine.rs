@@ -0,0 +1,84 @@
+use chess::{Game, SearchLimits};
+
+fn search(fen: &str, limits: SearchLimits) -> chess::SearchResult {
+    Game::from_fen(fen).unwrap().search(limits).unwrap().unwrap()
+}
+
+fn depth(depth: u32) -> SearchLimits {
+    SearchLimits {
+        depth: Some(depth),
+        ..SearchLimits::default()
+    }
+}
+
+#[test]
+fn finds_back_rank_mate() {
+    let result = search("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", depth(3));
+    assert_eq!((result.from.as_str(), result.to.as_str()), ("a1", "a8"));
+    assert!(result.score > 20_000);
+}
+
+#[test]
+fn finds_mate_in_two() {
+    // 1. Re8+ Rxe8 2. Rxe8#
+    let result = search("3r2k1/5ppp/8/8/8/8/4R3/4R1K1 w - - 0 1", depth(4));
+    assert_eq!(result.to, "e8");
+    assert!(result.score > 20_000, "{:?}", result);
+}
+
+#[test]
+fn takes_a_hanging_queen() {
+    let result = search("4k3/8/8/3q4/8/8/8/3QK3 w - - 0 1", depth(3));
+    assert_eq!((result.from.as_str(), result.to.as_str()), ("d1", "d5"));
+}
+
+#[test]
+fn promotes_to_a_queen() {
+    let result = search("8/P6k/8/8/8/8/8/K7 w - - 0 1", depth(2));
+    assert_eq!((result.from.as_str(), result.to.as_str()), ("a7", "a8"));
+    assert_eq!(result.promotion.as_deref(), Some("Queen"));
+}
+
+#[test]
+fn has_no_move_when_stalemated() {
+    let game = Game::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+    assert_eq!(game.search(depth(3)).unwrap(), None);
+}
+
+#[test]
+fn stops_at_the_node_limit() {
+    let limits = SearchLimits {
+        nodes: Some(5_000),
+        ..SearchLimits::default()
+    };
+    let result = search(
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        limits,
+    );
+    assert!(result.nodes <= 5_000, "{:?}", result);
+}
+
+#[test]
+fn stops_at_the_time_limit() {
+    let limits = SearchLimits {
+        time_ms: Some(100),
+        ..SearchLimits::default()
+    };
+    let started = std::time::Instant::now();
+    search(
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        limits,
+    );
+    assert!(started.elapsed().as_millis() < 1_000);
+}
+
+#[test]
+fn suggested_moves_can_be_played() {
+    let mut game = Game::init("standard".to_string()).unwrap();
+    for _ in 0..6 {
+        let result = game.search(depth(2)).unwrap().unwrap();
+        game.try_move(&result.from, &result.to, result.promotion.as_deref())
+            .unwrap();
+    }
+    assert_eq!(game.move_num(), 6);
+}
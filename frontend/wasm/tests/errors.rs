@@ -0,0 +1,19 @@
+use chess::Game;
+
+#[test]
+fn errors_carry_stable_codes() {
+    let mut game = Game::init("standard".to_string()).unwrap();
+    let err = game.try_move("e2", "e5", None).unwrap_err();
+    assert_eq!(err.code(), "illegal_move");
+    assert_eq!(
+        game.try_move("e9", "e4", None).unwrap_err().code(),
+        "invalid_coordinates"
+    );
+
+    let mut game = Game::from_fen("4k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    assert_eq!(game.try_move("a7", "a8", None).unwrap_err().code(), "missing_promotion");
+    assert_eq!(
+        game.try_move("a7", "a8", Some("Pawn")).unwrap_err().code(),
+        "invalid_promotion"
+    );
+}
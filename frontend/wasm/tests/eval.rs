@@ -0,0 +1,98 @@
+use chess::{EvalParams, Game, Weight};
+
+fn eval(fen: &str) -> i32 {
+    Game::from_fen(fen).unwrap().evaluate()
+}
+
+#[test]
+fn start_position_is_level() {
+    assert_eq!(Game::init("standard".to_string()).unwrap().evaluate(), 0);
+}
+
+#[test]
+fn mirrored_positions_negate() {
+    let pairs = [
+        (
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+            "rnbqkb1r/pppp1ppp/5n2/4p3/4P3/2N5/PPPP1PPP/R1BQKBNR b KQkq - 2 3",
+        ),
+        ("8/5k2/8/3P4/8/8/2K5/8 w - - 0 1", "8/2k5/8/8/3p4/8/5K2/8 b - - 0 1"),
+    ];
+    for (fen, mirrored) in pairs {
+        assert_eq!(eval(fen), -eval(mirrored), "{}", fen);
+    }
+}
+
+#[test]
+fn extra_material_counts_for_its_side() {
+    let queen_up = eval("rnb1kbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+    assert!(queen_up > 800, "{}", queen_up);
+    let knight_down = eval("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/R1BQKBNR w KQkq - 0 1");
+    assert!(knight_down < -250, "{}", knight_down);
+}
+
+#[test]
+fn passed_pawns_beat_blocked_ones() {
+    let passed = eval("4k3/8/8/3P4/8/8/8/4K3 w - - 0 1");
+    let opposed = eval("4k3/3p4/8/3P4/8/8/8/4K3 w - - 0 1");
+    // the opposed position also hands Black a pawn; the passer has to be worth more than that
+    assert!(passed > opposed + 100, "{} vs {}", passed, opposed);
+}
+
+#[test]
+fn broken_pawn_structure_costs() {
+    let healthy = eval("4k3/8/8/8/8/8/PPP5/4K3 w - - 0 1");
+    let doubled = eval("4k3/8/8/8/8/1P6/PP6/4K3 w - - 0 1");
+    let isolated = eval("4k3/8/8/8/8/8/P1PP4/4K3 w - - 0 1");
+    assert!(healthy > doubled, "{} vs {}", healthy, doubled);
+    assert!(healthy > isolated, "{} vs {}", healthy, isolated);
+}
+
+#[test]
+fn king_shelter_matters_in_the_middlegame() {
+    let sheltered = eval("r2q1rk1/ppp2ppp/2n5/8/8/2N5/PPP2PPP/R2Q1RK1 w - - 0 1");
+    let exposed = eval("r2q1rk1/ppp2ppp/2n5/8/8/2N3PP/PPP2P2/R2Q1RK1 w - - 0 1");
+    assert_eq!(sheltered, 0);
+    assert!(exposed < sheltered, "{}", exposed);
+}
+
+#[test]
+fn kings_head_for_the_centre_in_the_endgame() {
+    let central = eval("8/8/8/3k4/8/8/8/K7 w - - 0 1");
+    assert!(central < -50, "{}", central);
+}
+
+#[test]
+fn only_the_bare_king_placement_counts_against_a_queen() {
+    let cornered = eval("Q7/8/6k1/8/8/8/8/K7 w - - 0 1");
+    assert_eq!(cornered, eval("Q7/8/6k1/8/8/8/8/3K4 w - - 0 1"));
+    assert!(cornered > eval("Q7/8/8/4k3/8/8/8/K7 w - - 0 1"));
+}
+
+#[test]
+fn phase_blends_middlegame_and_endgame_weights() {
+    let params = EvalParams {
+        material: [
+            Weight::default(),
+            Weight { mg: 1000, eg: 0 },
+            Weight::default(),
+            Weight::default(),
+            Weight::default(),
+            Weight::default(),
+        ],
+        middlegame_squares: [[0; 64]; 6],
+        endgame_squares: [[0; 64]; 6],
+        doubled_pawn: Weight::default(),
+        isolated_pawn: Weight::default(),
+        passed_pawn: [Weight::default(); 8],
+        pawn_shield: Weight::default(),
+        king_attack: Weight::default(),
+        mobility: [Weight::default(); 6],
+        ..EvalParams::default()
+    };
+    // one queen is 4 of the starting 24 phase points
+    let game = Game::from_fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap();
+    assert_eq!(game.evaluate_with(&params), 1000 * 4 / 24);
+    let game = Game::init("standard".to_string()).unwrap();
+    assert_eq!(game.evaluate_with(&EvalParams::default()), game.evaluate());
+}
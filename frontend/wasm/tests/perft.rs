@@ -0,0 +1,136 @@
+//! Node counts from the Chess Programming Wiki's perft results and the Chess960 perft suite.
+
+use chess::Game;
+
+fn assert_perft(fen: &str, expected: &[u64]) {
+    let game = Game::from_fen(fen).unwrap();
+    for (depth, nodes) in expected.iter().enumerate() {
+        let depth = depth as u32 + 1;
+        assert_eq!(game.perft(depth).unwrap(), *nodes, "perft({}) of {}", depth, fen);
+    }
+}
+
+#[test]
+fn start_position() {
+    assert_perft(
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        &[20, 400, 8_902, 197_281],
+    );
+}
+
+#[test]
+fn new_game_matches_start_position_fen() {
+    let game = Game::init("standard".to_string()).unwrap();
+    assert_eq!(game.perft(3).unwrap(), 8_902);
+}
+
+#[test]
+fn kiwipete() {
+    assert_perft(
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        &[48, 2_039, 97_862, 4_085_603],
+    );
+}
+
+#[test]
+fn position_3() {
+    assert_perft(
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        &[14, 191, 2_812, 43_238, 674_624],
+    );
+}
+
+#[test]
+fn position_4() {
+    assert_perft(
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        &[6, 264, 9_467, 422_333],
+    );
+}
+
+#[test]
+fn position_4_mirrored() {
+    assert_perft(
+        "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+        &[6, 264, 9_467, 422_333],
+    );
+}
+
+#[test]
+fn position_5() {
+    assert_perft(
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        &[44, 1_486, 62_379, 2_103_487],
+    );
+}
+
+#[test]
+fn position_6() {
+    assert_perft(
+        "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+        &[46, 2_079, 89_890, 3_894_594],
+    );
+}
+
+#[test]
+fn chess960_position_1() {
+    assert_perft(
+        "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+        &[21, 528, 12_189, 326_672],
+    );
+}
+
+#[test]
+fn chess960_position_2() {
+    assert_perft(
+        "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+        &[21, 807, 18_002, 667_366],
+    );
+}
+
+#[test]
+fn chess960_position_3() {
+    assert_perft(
+        "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9",
+        &[20, 479, 10_471, 273_318],
+    );
+}
+
+#[test]
+fn divide_sums_to_perft() {
+    let game = Game::from_fen("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1").unwrap();
+    let divide = game.divide(3).unwrap();
+    assert_eq!(divide.len(), 6);
+    assert_eq!(
+        divide.iter().map(|(_, nodes)| nodes).sum::<u64>(),
+        game.perft(3).unwrap()
+    );
+}
+
+#[test]
+fn divide_names_promotions() {
+    let game = Game::from_fen("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8").unwrap();
+    let divide = game.divide(1).unwrap();
+    for promotion in ["d7c8q", "d7c8r", "d7c8b", "d7c8n"] {
+        assert!(divide.contains(&(promotion.to_string(), 1)), "{}", promotion);
+    }
+}
+
+#[test]
+fn from_fen_rejects_bad_input() {
+    for fen in [
+        "",
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP w KQkq - 0 1",
+        "rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 1",
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQ1BNR w kq - 0 1",
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e4 0 1",
+        // over-long ranks
+        "rnbqkbnr/ppppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "rnbqkbnr/pppppppp/44p/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNRR w KQkq - 0 1",
+        "rnbqkbnr/pppppppp/08/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    ] {
+        assert!(Game::from_fen(fen).is_err(), "{}", fen);
+    }
+}
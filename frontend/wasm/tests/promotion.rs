@@ -0,0 +1,39 @@
+use chess::{ChessError, Game};
+
+#[test]
+fn promotion_needs_a_piece_and_leaves_the_game_alone_without_one() {
+    let mut game = Game::from_fen("4k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    assert_eq!(game.try_move("a7", "a8", None), Err(ChessError::MissingPromotion));
+    assert_eq!(
+        game.try_move("a7", "a8", Some("King")),
+        Err(ChessError::InvalidPromotion("King".to_string()))
+    );
+    assert!(game.is_white_turn());
+    assert_eq!(game.get_legal_moves("a7").unwrap(), ["a8"]);
+}
+
+#[test]
+fn promotion_is_applied_with_the_move() {
+    let mut queen = Game::from_fen("4k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    let mut knight = queen;
+    queen.try_move("a7", "a8", Some("Queen")).unwrap();
+    knight.try_move("a7", "a8", Some("Knight")).unwrap();
+    assert!(queen.in_check("Black").unwrap());
+    assert!(!knight.in_check("Black").unwrap());
+}
+
+#[test]
+fn promoted_piece_belongs_to_the_side_that_moved() {
+    let mut game = Game::from_fen("4k3/8/8/8/8/8/p7/4K3 b - - 0 1").unwrap();
+    game.try_move("a2", "a1", Some("Rook")).unwrap();
+    assert!(game.in_check("White").unwrap());
+    assert!(!game.in_check("Black").unwrap());
+}
+
+#[test]
+fn promotion_piece_is_ignored_on_other_moves() {
+    let mut game = Game::from_fen("4k3/8/8/8/8/8/P7/4K3 w - - 0 1").unwrap();
+    assert!(!game.is_promotion("a2", "a4").unwrap());
+    game.try_move("a2", "a4", Some("Queen")).unwrap();
+    assert!(game.is_black_turn());
+}
@@ -0,0 +1,162 @@
+use argon2::{
+    password_hash::{self, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Bytes of randomness in an invite code; 128 bits is far beyond guessing range.
+const INVITE_CODE_BYTES: usize = 16;
+const SALT_BYTES: usize = 16;
+
+/// Who may take a seat in a room. The default lets anyone in.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RoomAccess {
+    password: Option<PasswordHash>,
+    /// When set, the last free seat is kept for this player id.
+    pub invited_player: Option<String>,
+    /// Handed to each player of a correspondence game as they first take their seat. Moves posted
+    /// without a connection have to carry it, since player ids are no secret.
+    #[serde(default)]
+    seat_tokens: HashMap<String, SeatToken>,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+struct SeatToken(String);
+
+impl std::fmt::Debug for SeatToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SeatToken(..)")
+    }
+}
+
+/// A room password hashed with Argon2id, so snapshots on disk never hold the password itself and
+/// a leaked one is expensive to brute-force. The PHC string carries its own salt and parameters.
+#[derive(Clone, Serialize, Deserialize)]
+struct PasswordHash {
+    phc: String,
+}
+
+impl std::fmt::Debug for PasswordHash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("PasswordHash(..)")
+    }
+}
+
+impl PasswordHash {
+    fn new(password: &str) -> PasswordHash {
+        let salt = SaltString::encode_b64(&random_bytes::<SALT_BYTES>()).expect("salt is a valid length");
+        let phc = Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .expect("default Argon2 parameters are valid")
+            .to_string();
+        PasswordHash { phc }
+    }
+
+    fn matches(&self, password: &str) -> bool {
+        match password_hash::PasswordHash::new(&self.phc) {
+            Ok(phc) => Argon2::default().verify_password(password.as_bytes(), &phc).is_ok(),
+            Err(err) => {
+                tracing::error!(%err, "unreadable room password hash");
+                false
+            }
+        }
+    }
+}
+
+impl RoomAccess {
+    pub fn new(password: Option<&str>, invited_player: Option<String>) -> RoomAccess {
+        RoomAccess {
+            password: password.map(PasswordHash::new),
+            invited_player,
+            seat_tokens: HashMap::new(),
+        }
+    }
+
+    /// A fresh token for `id`'s seat, replacing any earlier one.
+    pub fn issue_seat_token(&mut self, id: &str) -> String {
+        let token = invite_code();
+        self.seat_tokens.insert(id.to_string(), SeatToken(token.clone()));
+        token
+    }
+
+    /// True when `token` is the one issued for `id`'s seat, compared in constant time.
+    pub fn check_seat_token(&self, id: &str, token: &str) -> bool {
+        let Some(SeatToken(issued)) = self.seat_tokens.get(id) else {
+            return false;
+        };
+        issued.len() == token.len() && issued.bytes().zip(token.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+    }
+
+    pub fn is_password_protected(&self) -> bool {
+        self.password.is_some()
+    }
+
+    /// True for open rooms, or when `password` is the room's password. Argon2 is slow on purpose,
+    /// so the hash is checked on the blocking pool rather than holding up the runtime.
+    pub async fn verify_password(&self, password: Option<&str>) -> bool {
+        let (hash, password) = match (&self.password, password) {
+            (None, _) => return true,
+            (Some(hash), Some(password)) => (hash.clone(), password.to_string()),
+            (Some(_), None) => return false,
+        };
+        match tokio::task::spawn_blocking(move || hash.matches(&password)).await {
+            Ok(matches) => matches,
+            Err(err) => {
+                tracing::error!(%err, "password check panicked");
+                false
+            }
+        }
+    }
+}
+
+/// A fresh, unguessable room id for a room created through `POST /rooms`.
+pub fn invite_code() -> String {
+    hex(&random_bytes::<INVITE_CODE_BYTES>())
+}
+
+fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut bytes = [0; N];
+    OsRng.fill_bytes(&mut bytes);
+    bytes
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn passwords_are_stored_as_argon2() {
+        let access = RoomAccess::new(Some("hunter2"), None);
+        let saved = serde_json::to_string(&access).unwrap();
+        assert!(saved.contains("$argon2id$"), "{}", saved);
+        assert!(!saved.contains("hunter2"), "{}", saved);
+
+        let restored: RoomAccess = serde_json::from_str(&saved).unwrap();
+        assert!(restored.verify_password(Some("hunter2")).await);
+        assert!(!restored.verify_password(Some("hunter3")).await);
+        assert!(!restored.verify_password(None).await);
+        assert!(RoomAccess::default().verify_password(None).await);
+    }
+
+    #[test]
+    fn seat_tokens_only_open_their_own_seat() {
+        let mut access = RoomAccess::default();
+        let a = access.issue_seat_token("a");
+        let b = access.issue_seat_token("b");
+        assert_ne!(a, b);
+        assert!(access.check_seat_token("a", &a));
+        assert!(!access.check_seat_token("a", &b));
+        assert!(!access.check_seat_token("c", &a));
+        assert!(!access.check_seat_token("a", ""));
+
+        let restored: RoomAccess = serde_json::from_str(&serde_json::to_string(&access).unwrap()).unwrap();
+        assert!(restored.check_seat_token("b", &b));
+    }
+}
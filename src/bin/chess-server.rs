@@ -0,0 +1,15 @@
+use chess_ws_api::{config::Config, shutdown, telemetry, Server};
+use tokio::net::TcpListener;
+
+/// Runs the server on its own, without Shuttle. Settings come from flags or the environment;
+/// see `--help`.
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    telemetry::init();
+    let config = Config::load();
+
+    let listener = TcpListener::bind(config.bind).await?;
+    tracing::info!(addr = %config.bind, "listening");
+
+    Server::build(config).await.serve(listener, shutdown::signal()).await
+}
@@ -0,0 +1,155 @@
+//! Engine players seated by the server. A bot joins a room like anyone else and sends its moves
+//! through `RoomHandle::relay`, so they are checked, clocked and broadcast like a human's.
+
+use crate::{
+    message::WsMessage,
+    room::{RoomError, RoomHandle, RoomInfo},
+};
+use axum::extract::ws::Message;
+use serde_json::json;
+use tokio::sync::mpsc;
+use tracing::Instrument;
+
+/// The id bots play under, so a room holds at most one.
+pub const BOT_ID: &str = "bot";
+
+/// Seats a bot playing at `level`, which has been validated, opposite the player already in the
+/// room. It plays out the room's games until the last human leaves or the room closes.
+pub async fn seat(handle: RoomHandle, room: &str, level: u8, password_checked: bool) -> Result<(), RoomError> {
+    // the first seat is the creator's, whose colour preference the room follows
+    if handle.info().await?.players.is_empty() {
+        return Err(RoomError::NobodyToPlay);
+    }
+    let outbox = handle.join(BOT_ID, password_checked).await?;
+    let span = tracing::info_span!(parent: None, "bot", room, level);
+    tokio::spawn(play(handle, level, outbox).instrument(span));
+    Ok(())
+}
+
+/// Looks at the room after everything it sends the bot, and searches on the blocking pool
+/// whenever it's the bot's turn.
+async fn play(handle: RoomHandle, level: u8, mut outbox: mpsc::Receiver<Message>) {
+    tracing::info!("bot joined");
+    while let Some(message) = outbox.recv().await {
+        let Message::Text(text) = message else {
+            continue;
+        };
+        let Ok(room_info) = handle.info().await else {
+            break;
+        };
+        if message_type(&text).as_deref() == Some("leave") && room_info.players.iter().all(|id| id == BOT_ID) {
+            let _ = handle.leave(BOT_ID).await;
+            break;
+        }
+        if !bot_to_move(&room_info) {
+            continue;
+        }
+
+        let game = room_info.game;
+        let seed = rand::random();
+        let result = match tokio::task::spawn_blocking(move || game.bot_search(level, seed)).await {
+            Ok(Ok(Some(result))) => result,
+            // no legal moves, which the room has already dealt with
+            Ok(Ok(None)) => continue,
+            Ok(Err(err)) => {
+                tracing::error!(%err, "bot search failed");
+                continue;
+            }
+            Err(err) => {
+                tracing::error!(%err, "bot search panicked");
+                continue;
+            }
+        };
+        let data = json!({
+            "message_type": "move",
+            "start_sq_coords": result.from,
+            "end_sq_coords": result.to,
+            "lastPawnAction": result.promotion.unwrap_or_default(),
+        });
+        let text = json!(WsMessage {
+            sender_id: BOT_ID.to_string(),
+            data: data.to_string(),
+        })
+        .to_string();
+        if handle.relay(BOT_ID, text).await.is_err() {
+            break;
+        }
+    }
+    tracing::info!("bot left");
+}
+
+/// The room settles colours as soon as the bot takes its seat.
+fn bot_to_move(room_info: &RoomInfo) -> bool {
+    match &room_info.white {
+        Some(white) => (white == BOT_ID) == room_info.game.is_white_turn(),
+        None => false,
+    }
+}
+
+fn message_type(text: &str) -> Option<String> {
+    let ws_message: WsMessage = serde_json::from_str(text).ok()?;
+    let data: serde_json::Value = serde_json::from_str(&ws_message.data).ok()?;
+    data["message_type"].as_str().map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{access::RoomAccess, registry::Registry, settings::RoomSettings};
+    use std::time::Duration;
+    use tokio::time::{sleep, timeout};
+
+    fn move_frame(id: &str, start: &str, end: &str) -> String {
+        let data =
+            json!({ "message_type": "move", "start_sq_coords": start, "end_sq_coords": end, "lastPawnAction": "" });
+        json!({ "sender_id": id, "data": data.to_string() }).to_string()
+    }
+
+    #[tokio::test]
+    async fn bot_answers_moves_and_leaves_with_its_opponent() {
+        let registry = Registry::default();
+        let empty = registry.create(RoomSettings::implicit("standard", None), RoomAccess::default());
+        let empty = registry.get(&empty).unwrap();
+        assert_eq!(seat(empty, "empty", 1, false).await, Err(RoomError::NobodyToPlay));
+
+        let (handle, mut outbox) = registry.join("room", "a", "standard", None, false).await.unwrap();
+        seat(handle.clone(), "room", 1, false).await.unwrap();
+        assert_eq!(
+            handle.info().await.unwrap().players,
+            vec!["a".to_string(), BOT_ID.to_string()]
+        );
+
+        let white = handle
+            .info()
+            .await
+            .unwrap()
+            .white
+            .expect("seating the bot should settle colours");
+        if white == "a" {
+            handle.relay("a", move_frame("a", "e2", "e4")).await.unwrap();
+        }
+        let reply = timeout(Duration::from_secs(5), async {
+            while let Some(Message::Text(text)) = outbox.recv().await {
+                let ws_message: WsMessage = serde_json::from_str(&text).unwrap();
+                if ws_message.sender_id == BOT_ID && message_type(&text).as_deref() == Some("move") {
+                    return text;
+                }
+            }
+            panic!("outbox closed");
+        })
+        .await
+        .expect("the bot should reply");
+        assert!(reply.contains("start_sq_coords"), "{} should be a move", reply);
+        let room_info = handle.info().await.unwrap();
+        assert_eq!(room_info.game.move_num(), if white == "a" { 2 } else { 1 });
+
+        assert_eq!(handle.leave("a").await, Ok(crate::room::Departure::Left));
+        for _ in 0..50 {
+            if registry.get("room").is_none() {
+                return;
+            }
+            sleep(Duration::from_millis(20)).await;
+        }
+        panic!("the bot should leave an empty room");
+    }
+}
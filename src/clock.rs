@@ -0,0 +1,185 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::time::Instant;
+
+const MAX_INITIAL_SECS: u64 = 3 * 60 * 60;
+const MAX_INCREMENT_SECS: u64 = 60;
+const MAX_DAYS_PER_MOVE: u64 = 14;
+
+/// Base time plus per-move increment.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TimeControl {
+    pub initial_secs: u64,
+    pub increment_secs: u64,
+}
+
+impl TimeControl {
+    pub fn new(initial_secs: u64, increment_secs: u64) -> Result<TimeControl, String> {
+        if initial_secs == 0 || initial_secs > MAX_INITIAL_SECS || increment_secs > MAX_INCREMENT_SECS {
+            return Err(format!(
+                "Time control must be 1-{} seconds plus 0-{} seconds per move",
+                MAX_INITIAL_SECS, MAX_INCREMENT_SECS
+            ));
+        }
+
+        Ok(TimeControl {
+            initial_secs,
+            increment_secs,
+        })
+    }
+}
+
+/// Pace of a correspondence game: each move is due within this many days of the last one.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Correspondence {
+    pub days_per_move: u64,
+}
+
+impl Correspondence {
+    pub fn new(days_per_move: u64) -> Result<Correspondence, String> {
+        if days_per_move == 0 || days_per_move > MAX_DAYS_PER_MOVE {
+            return Err(format!(
+                "Correspondence games allow 1-{} days per move",
+                MAX_DAYS_PER_MOVE
+            ));
+        }
+
+        Ok(Correspondence { days_per_move })
+    }
+
+    pub fn per_move(&self) -> Duration {
+        Duration::from_secs(self.days_per_move * 24 * 60 * 60)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Side {
+    White,
+    Black,
+}
+
+impl Side {
+    pub fn other(&self) -> Side {
+        match self {
+            Side::White => Side::Black,
+            Side::Black => Side::White,
+        }
+    }
+}
+
+/// Chess clock for one game. It starts running with the first move, so neither side loses time
+/// while waiting for an opponent.
+#[derive(Debug, Clone)]
+pub struct Clock {
+    time_control: TimeControl,
+    white: Duration,
+    black: Duration,
+    running: Option<(Side, Instant)>,
+    paused: Option<Side>,
+}
+
+/// Clock state that survives a restart. Times are frozen at the moment the snapshot was taken.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ClockSnapshot {
+    pub white_ms: u64,
+    pub black_ms: u64,
+    pub running: Option<Side>,
+}
+
+impl Clock {
+    pub fn new(time_control: TimeControl) -> Clock {
+        let initial = Duration::from_secs(time_control.initial_secs);
+        Clock {
+            time_control,
+            white: initial,
+            black: initial,
+            running: None,
+            paused: None,
+        }
+    }
+
+    /// Rebuilds a clock from a snapshot. It stays paused until `resume` so nobody loses time
+    /// while reconnecting.
+    pub fn restore(time_control: TimeControl, snapshot: ClockSnapshot) -> Clock {
+        Clock {
+            time_control,
+            white: Duration::from_millis(snapshot.white_ms),
+            black: Duration::from_millis(snapshot.black_ms),
+            running: None,
+            paused: snapshot.running,
+        }
+    }
+
+    pub fn snapshot(&self) -> ClockSnapshot {
+        ClockSnapshot {
+            white_ms: self.remaining(Side::White).as_millis() as u64,
+            black_ms: self.remaining(Side::Black).as_millis() as u64,
+            running: self.running.map(|(side, _)| side).or(self.paused),
+        }
+    }
+
+    pub fn resume(&mut self) {
+        if let Some(side) = self.paused.take() {
+            self.running = Some((side, Instant::now()));
+        }
+    }
+
+    fn remaining_mut(&mut self, side: Side) -> &mut Duration {
+        match side {
+            Side::White => &mut self.white,
+            Side::Black => &mut self.black,
+        }
+    }
+
+    /// Time left for `side`, counting the currently running period.
+    pub fn remaining(&self, side: Side) -> Duration {
+        let stored = match side {
+            Side::White => self.white,
+            Side::Black => self.black,
+        };
+        match self.running {
+            Some((running_side, since)) if running_side == side => stored.saturating_sub(since.elapsed()),
+            _ => stored,
+        }
+    }
+
+    /// `mover` finished their move: charge them the elapsed time, add the increment and start the
+    /// opponent's clock. Callers check `flagged` first.
+    pub fn press(&mut self, mover: Side) {
+        let now = Instant::now();
+        if let Some((running_side, since)) = self.running {
+            if running_side == mover {
+                let left = self.remaining_mut(mover);
+                *left = left.saturating_sub(now.duration_since(since));
+            }
+        }
+
+        let increment = Duration::from_secs(self.time_control.increment_secs);
+        *self.remaining_mut(mover) += increment;
+        self.running = Some((mover.other(), now));
+        self.paused = None;
+    }
+
+    /// When the side to move runs out of time, if the clock is running.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.running.map(|(side, since)| {
+            let stored = match side {
+                Side::White => self.white,
+                Side::Black => self.black,
+            };
+            since + stored
+        })
+    }
+
+    /// Side whose time has run out, if any.
+    pub fn flagged(&self) -> Option<Side> {
+        match self.running {
+            Some((side, _)) if self.remaining(side).is_zero() => Some(side),
+            _ => None,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Clock::new(self.time_control);
+    }
+}
@@ -0,0 +1,102 @@
+use crate::{cors::CorsConfig, limits::Limits};
+use clap::{error::ErrorKind, CommandFactory, Parser};
+use std::{
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+};
+
+/// Server settings. Every flag can also be set through the environment variable named next to it.
+#[derive(Debug, Clone, Parser)]
+#[command(version, about = "Websocket server for online chess games")]
+pub struct Config {
+    /// Address to listen on
+    #[arg(long, env = "BIND_ADDR", default_value = "0.0.0.0:8000")]
+    pub bind: SocketAddr,
+
+    /// Comma separated addresses of reverse proxies whose `X-Forwarded-For` header is trusted to
+    /// name the client
+    #[arg(long, env = "TRUSTED_PROXIES", value_delimiter = ',')]
+    pub trusted_proxies: Vec<IpAddr>,
+
+    /// Seconds between server pings
+    #[arg(long, env = "PAUSE_SECS", default_value_t = 15, value_parser = clap::value_parser!(u64).range(1..))]
+    pub pause_secs: u64,
+
+    /// Seconds between websocket pings sent to each client
+    #[arg(long, env = "WS_PING_SECS", default_value_t = 10, value_parser = clap::value_parser!(u64).range(1..))]
+    pub ws_ping_secs: u64,
+
+    /// Seconds a client may go without answering a ping before its connection is dropped. At least
+    /// the ping interval
+    #[arg(long, env = "WS_PONG_TIMEOUT_SECS", default_value_t = 30, value_parser = clap::value_parser!(u64).range(1..))]
+    pub ws_pong_timeout_secs: u64,
+
+    /// Seconds a room may wait for a second player before it is closed
+    #[arg(long, env = "WAITING_ROOM_TTL_SECS", default_value_t = 30 * 60)]
+    pub waiting_room_ttl_secs: u64,
+
+    /// Seconds a full room may go without a move before it is closed
+    #[arg(long, env = "IDLE_GAME_TTL_SECS", default_value_t = 60 * 60)]
+    pub idle_game_ttl_secs: u64,
+
+    /// Seconds between sweeps for expired rooms
+    #[arg(long, env = "REAP_INTERVAL_SECS", default_value_t = 60, value_parser = clap::value_parser!(u64).range(1..))]
+    pub reap_interval_secs: u64,
+
+    /// Where rooms are saved on shutdown and restored from on startup
+    #[arg(long, env = "SNAPSHOT_PATH", default_value = "snapshots/rooms.json")]
+    pub snapshot_path: PathBuf,
+
+    /// Directory correspondence games are saved to after every move
+    #[arg(long, env = "GAMES_DIR", default_value = "snapshots/games")]
+    pub games_dir: PathBuf,
+
+    #[command(flatten)]
+    pub cors: CorsConfig,
+
+    #[command(flatten)]
+    pub limits: Limits,
+}
+
+impl Config {
+    /// Reads flags and the environment, exiting with a usage error if they don't make sense.
+    pub fn load() -> Config {
+        Config::parse().validated().unwrap_or_else(|err| err.exit())
+    }
+
+    /// Reads the configuration from the environment alone, for runtimes that own the command line.
+    pub fn from_env() -> Result<Config, clap::Error> {
+        Config::try_parse_from([env!("CARGO_PKG_NAME")])?.validated()
+    }
+
+    /// Checks that relate one setting to another, which clap can't express.
+    fn validated(self) -> Result<Config, clap::Error> {
+        // a pong can't arrive before the ping it answers is sent
+        if self.ws_pong_timeout_secs < self.ws_ping_secs {
+            let message = format!(
+                "--ws-pong-timeout-secs ({}) must be at least --ws-ping-secs ({})",
+                self.ws_pong_timeout_secs, self.ws_ping_secs
+            );
+            return Err(Config::command().error(ErrorKind::ValueValidation, message));
+        }
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Config, clap::Error> {
+        Config::try_parse_from([&["chess-server"], args].concat())?.validated()
+    }
+
+    #[test]
+    fn pong_timeout_must_cover_the_ping_interval() {
+        assert!(parse(&[]).is_ok());
+        assert!(parse(&["--ws-ping-secs", "10", "--ws-pong-timeout-secs", "10"]).is_ok());
+        assert!(parse(&["--ws-pong-timeout-secs", "0"]).is_err());
+        let err = parse(&["--ws-ping-secs", "10", "--ws-pong-timeout-secs", "5"]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ValueValidation);
+    }
+}
@@ -0,0 +1,64 @@
+use axum::http::{HeaderName, HeaderValue, Method};
+use clap::Args;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+/// Which browser origins may use the API. The same allowlist covers HTTP routes, through the CORS
+/// layer, and websocket upgrades, which browsers don't preflight and so are checked by hand.
+#[derive(Debug, Clone, Args)]
+pub struct CorsConfig {
+    /// Comma separated origins allowed to call the API and open websockets
+    #[arg(long, env = "CORS_ORIGINS", value_delimiter = ',', value_parser = parse_origin)]
+    pub cors_origins: Vec<HeaderValue>,
+
+    /// Comma separated methods allowed on cross-origin requests
+    #[arg(long, env = "CORS_METHODS", value_delimiter = ',', default_value = "GET,POST", value_parser = parse_method)]
+    pub cors_methods: Vec<Method>,
+
+    /// Comma separated request headers allowed on cross-origin requests
+    #[arg(long, env = "CORS_HEADERS", value_delimiter = ',', default_value = "content-type", value_parser = parse_header)]
+    pub cors_headers: Vec<HeaderName>,
+
+    /// Allow every origin, method and header. For local development only
+    #[arg(long, env = "CORS_DEV_PERMISSIVE")]
+    pub cors_dev_permissive: bool,
+}
+
+impl CorsConfig {
+    pub fn layer(&self) -> CorsLayer {
+        if self.cors_dev_permissive {
+            return CorsLayer::permissive();
+        }
+        CorsLayer::new()
+            .allow_origin(AllowOrigin::list(self.cors_origins.iter().cloned()))
+            .allow_methods(self.cors_methods.clone())
+            .allow_headers(self.cors_headers.clone())
+    }
+
+    /// Whether a websocket upgrade carrying `origin` may go ahead. Requests without an `Origin`
+    /// header don't come from a browser page, so there is no other site to protect against.
+    pub fn allows_origin(&self, origin: Option<&HeaderValue>) -> bool {
+        match origin {
+            Some(origin) => self.cors_dev_permissive || self.cors_origins.contains(origin),
+            None => true,
+        }
+    }
+}
+
+fn parse_origin(origin: &str) -> Result<HeaderValue, String> {
+    HeaderValue::from_str(origin.trim()).map_err(|err| err.to_string())
+}
+
+fn parse_method(method: &str) -> Result<Method, String> {
+    method
+        .trim()
+        .to_uppercase()
+        .parse()
+        .map_err(|_| format!("invalid method: {}", method))
+}
+
+fn parse_header(header: &str) -> Result<HeaderName, String> {
+    header
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid header name: {}", header))
+}
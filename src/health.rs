@@ -0,0 +1,90 @@
+use crate::State;
+use axum::{http::StatusCode, response::IntoResponse, Extension, Json};
+use serde_json::json;
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// Records when the ping loop last ran, so readiness can tell a stuck or dead loop apart from a
+/// quiet server.
+#[derive(Debug, Clone)]
+pub struct Heartbeat {
+    started: Instant,
+    last_beat_ms: Arc<AtomicU64>,
+}
+
+impl Default for Heartbeat {
+    fn default() -> Heartbeat {
+        Heartbeat {
+            started: Instant::now(),
+            last_beat_ms: Arc::new(AtomicU64::new(0)),
+        }
+    }
+}
+
+impl Heartbeat {
+    pub fn beat(&self) {
+        self.last_beat_ms
+            .store(self.started.elapsed().as_millis() as u64, Ordering::Relaxed);
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    pub fn since_last_beat(&self) -> Duration {
+        self.uptime()
+            .saturating_sub(Duration::from_millis(self.last_beat_ms.load(Ordering::Relaxed)))
+    }
+}
+
+fn status(state: &State) -> serde_json::Value {
+    json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "uptime_secs": state.heartbeat.uptime().as_secs(),
+        "rooms": state.registry.room_count(),
+        "connections": state.registry.metrics().connections.get(),
+    })
+}
+
+/// Liveness: the process is up and serving requests.
+pub async fn healthz(Extension(state): Extension<State>) -> impl IntoResponse {
+    let mut body = status(&state);
+    body["status"] = json!("ok");
+    Json(body)
+}
+
+/// Readiness: snapshots can be written, the ping loop is still running and the server isn't
+/// draining for a restart.
+pub async fn readyz(Extension(state): Extension<State>) -> impl IntoResponse {
+    let storage = match state.snapshots.check().await {
+        Ok(()) => "ok".to_string(),
+        Err(err) => err.to_string(),
+    };
+    // a missed tick is fine, two in a row means the loop is stuck or gone
+    let ping_loop = if state.heartbeat.since_last_beat() <= state.pause * 2 {
+        "ok"
+    } else {
+        "stalled"
+    };
+    let shutting_down = state.shutting_down.load(Ordering::SeqCst);
+    let ready = storage == "ok" && ping_loop == "ok" && !shutting_down;
+
+    let mut body = status(&state);
+    body["status"] = json!(if ready { "ok" } else { "unavailable" });
+    body["checks"] = json!({
+        "storage": storage,
+        "ping_loop": ping_loop,
+        "shutting_down": shutting_down,
+    });
+    let code = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (code, Json(body))
+}
@@ -0,0 +1,765 @@
+use access::RoomAccess;
+use axum::{
+    extract::{
+        ws::{close_code, CloseFrame, Message, WebSocket},
+        ConnectInfo, Path, Query, WebSocketUpgrade,
+    },
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use clock::TimeControl;
+use config::Config;
+use cors::CorsConfig;
+use futures::{future::join_all, SinkExt, StreamExt};
+use health::Heartbeat;
+use limits::{ConnectionLimiter, IpRateLimiter, Limits};
+use message::sys_message;
+use registry::Registry;
+use room::{parse_frame, Departure, MoveData, RoomError, RoomHandle, RoomTtls};
+use serde::Deserialize;
+use serde_json::json;
+use settings::{ColourPreference, RoomSettings, Visibility};
+use std::{
+    future::Future,
+    io,
+    net::{IpAddr, SocketAddr},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use storage::{GameStore, SnapshotStore};
+use tokio::{
+    net::TcpListener,
+    sync::{mpsc, watch},
+    time::{interval_at, sleep, MissedTickBehavior},
+};
+use tracing::Instrument;
+use validation::{
+    validate_bot_level, validate_correspondence, validate_password, validate_player_id, validate_room_id,
+    validate_rule_set, validate_time_control, validate_variant, JoinRejection,
+};
+
+mod access;
+mod bot;
+mod clock;
+pub mod config;
+pub mod cors;
+mod health;
+pub mod limits;
+mod message;
+mod metrics;
+mod reaper;
+mod registry;
+mod room;
+mod settings;
+pub mod shutdown;
+mod storage;
+pub mod telemetry;
+mod validation;
+
+/// Shared by every handler. Rooms live in their own tasks, so nothing here needs a global lock.
+#[derive(Debug, Clone)]
+struct State {
+    registry: Registry,
+    global_rx: watch::Receiver<Message>,
+    limits: Limits,
+    ip_limiter: IpRateLimiter,
+    shutting_down: Arc<AtomicBool>,
+    snapshots: SnapshotStore,
+    heartbeat: Heartbeat,
+    pause: Duration,
+    ws_ping: Duration,
+    ws_pong_timeout: Duration,
+    cors: CorsConfig,
+    trusted_proxies: Vec<IpAddr>,
+}
+
+/// The router plus the state needed to drain it, so shutdown can save rooms before exiting.
+pub struct Server {
+    router: Router,
+    state: State,
+}
+
+impl Server {
+    /// Restores rooms saved by the last shutdown and any correspondence games, starts the ping
+    /// loop and builds the router.
+    pub async fn build(config: Config) -> Server {
+        let (global_tx, global_rx) = watch::channel(Message::Text("{}".to_string()));
+        let games = GameStore::new(&config.games_dir);
+
+        let state = State {
+            registry: Registry::with_games(games.clone().spawn_writer()),
+            global_rx,
+            limits: config.limits,
+            ip_limiter: IpRateLimiter::default(),
+            shutting_down: Arc::new(AtomicBool::new(false)),
+            snapshots: SnapshotStore::new(&config.snapshot_path),
+            heartbeat: Heartbeat::default(),
+            pause: Duration::from_secs(config.pause_secs),
+            ws_ping: Duration::from_secs(config.ws_ping_secs),
+            ws_pong_timeout: Duration::from_secs(config.ws_pong_timeout_secs),
+            cors: config.cors.clone(),
+            trusted_proxies: config.trusted_proxies.clone(),
+        };
+
+        match state.snapshots.take().await {
+            Ok(rooms) => {
+                tracing::info!(count = rooms.len(), "restoring rooms");
+                for room in rooms {
+                    state.registry.restore(room);
+                }
+            }
+            Err(err) => tracing::error!(%err, "failed to restore rooms"),
+        }
+        // games saved by the shutdown above are already running and are skipped
+        match games.load().await {
+            Ok(games) => {
+                tracing::info!(count = games.len(), "restoring correspondence games");
+                for game in games {
+                    state.registry.restore(game);
+                }
+            }
+            Err(err) => tracing::error!(%err, "failed to restore correspondence games"),
+        }
+
+        let state_send = state.clone();
+        tokio::spawn(async move {
+            loop {
+                let msg = sys_message("server", "ping", "is_up");
+                tracing::info!(
+                    clients = state_send.registry.metrics().connections.get(),
+                    "clients count"
+                );
+                state_send.ip_limiter.prune();
+                state_send.heartbeat.beat();
+
+                if global_tx.send(Message::Text(msg)).is_err() {
+                    break;
+                }
+
+                sleep(state_send.pause).await;
+            }
+        });
+
+        let ttls = RoomTtls {
+            waiting: Duration::from_secs(config.waiting_room_ttl_secs),
+            idle_game: Duration::from_secs(config.idle_game_ttl_secs),
+        };
+        reaper::spawn(
+            state.registry.clone(),
+            ttls,
+            Duration::from_secs(config.reap_interval_secs),
+        );
+
+        if config.cors.cors_dev_permissive {
+            tracing::warn!("CORS is permissive: every origin may use the API");
+        }
+
+        let router = Router::new()
+            .route("/websocket/:room/:id/:rule_set", get(websocket_handler))
+            .route("/websocket/:room/:id", get(join_room_handler))
+            .route("/rooms", get(list_rooms).post(create_room))
+            .route("/rooms/:room_id/game", get(get_game))
+            .route("/rooms/:room_id/moves", post(post_move))
+            .route("/rooms/:room_id/bot", post(add_bot))
+            .route("/getroomrules/:room_id", get(get_room_rules))
+            .route("/healthz", get(health::healthz))
+            .route("/readyz", get(health::readyz))
+            .route("/metrics", get(metrics))
+            .layer(config.cors.layer())
+            .layer(Extension(state.clone()));
+
+        Server { router, state }
+    }
+
+    /// Serves until `signal` resolves, then drains the rooms and saves them for the next start.
+    pub async fn serve(
+        self,
+        listener: TcpListener,
+        signal: impl Future<Output = ()> + Send + 'static,
+    ) -> io::Result<()> {
+        let state = self.state;
+        axum::serve(
+            listener,
+            self.router.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .with_graceful_shutdown(async move {
+            signal.await;
+            shutdown::drain(&state).await;
+        })
+        .await
+    }
+}
+
+async fn metrics(Extension(state): Extension<State>) -> Response {
+    match state.registry.metrics().render() {
+        Ok(body) => ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body).into_response(),
+        Err(err) => {
+            tracing::error!(%err, "failed to render metrics");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct CreateRoomRequest {
+    rule_set: String,
+    initial_secs: Option<u64>,
+    increment_secs: Option<u64>,
+    days_per_move: Option<u64>,
+    #[serde(default)]
+    rated: bool,
+    #[serde(default)]
+    colour: ColourPreference,
+    #[serde(default)]
+    visibility: Visibility,
+    #[serde(default)]
+    variant: serde_json::Map<String, serde_json::Value>,
+    password: Option<String>,
+    invited_player: Option<String>,
+}
+
+async fn create_room(
+    Extension(state): Extension<State>,
+    Json(request): Json<CreateRoomRequest>,
+) -> Result<Response, JoinRejection> {
+    if state.shutting_down.load(Ordering::SeqCst) {
+        return Err(JoinRejection::ShuttingDown);
+    }
+    validate_rule_set(&request.rule_set)?;
+    let time_control = validate_time_control(request.initial_secs, request.increment_secs)?;
+    let correspondence = validate_correspondence(request.days_per_move, time_control)?;
+    validate_variant(&request.variant)?;
+    if let Some(password) = &request.password {
+        validate_password(password)?;
+    }
+    if let Some(invited_player) = &request.invited_player {
+        validate_player_id(invited_player)?;
+    }
+
+    let settings = RoomSettings {
+        rule_set: request.rule_set,
+        time_control,
+        correspondence,
+        rated: request.rated,
+        colour: Some(request.colour.resolve()),
+        visibility: request.visibility,
+        variant: request.variant,
+    };
+    let access = RoomAccess::new(request.password.as_deref(), request.invited_player);
+    let room_id = state.registry.create(settings.clone(), access);
+    Ok((
+        StatusCode::CREATED,
+        Json(json!({ "room_id": room_id, "settings": settings })),
+    )
+        .into_response())
+}
+
+/// Public rooms with a free seat and no password, for players looking for a game.
+async fn list_rooms(Extension(state): Extension<State>) -> impl IntoResponse {
+    let handles = state.registry.handles();
+    let infos = join_all(handles.iter().map(|handle| handle.info())).await;
+    let rooms: Vec<_> = infos
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|room_info| {
+            room_info.settings.visibility == Visibility::Public
+                && !room_info.access.is_password_protected()
+                && room_info.players.len() < 2
+        })
+        .map(|room_info| json!({ "room_id": room_info.room, "players": room_info.players.len(), "settings": room_info.settings }))
+        .collect();
+
+    Json(json!({ "rooms": rooms }))
+}
+
+#[derive(Deserialize)]
+struct RoomRulesParams {
+    password: Option<String>,
+}
+
+/// Password protected rooms look like missing ones unless the right password is given, so the
+/// endpoint can't be used to probe for them.
+async fn get_room_rules(
+    Path(room_id): Path<String>,
+    Query(params): Query<RoomRulesParams>,
+    Extension(state): Extension<State>,
+) -> impl IntoResponse {
+    let room_info = match state.registry.get(&room_id) {
+        Some(handle) => handle.info().await.ok(),
+        None => None,
+    };
+    let room_info = match room_info {
+        Some(room_info) if room_info.access.verify_password(params.password.as_deref()).await => room_info,
+        _ => return Err(Json(json!({"error": "Room does not exist".to_string()}))),
+    };
+
+    Ok(Json(json!({
+        "rule_set": room_info.settings.rule_set,
+        "time_control": room_info.settings.time_control,
+        "settings": room_info.settings,
+    })))
+}
+
+/// A room's current position, for players checking in on a correspondence game between moves.
+async fn get_game(
+    Path(room_id): Path<String>,
+    Query(params): Query<RoomRulesParams>,
+    Extension(state): Extension<State>,
+) -> Result<Response, RoomError> {
+    let handle = state.registry.get(&room_id).ok_or(RoomError::RoomNotFound)?;
+    let room_info = handle.info().await?;
+    if !room_info.access.verify_password(params.password.as_deref()).await {
+        return Err(RoomError::RoomNotFound);
+    }
+    let game = match room_info.game.dump() {
+        Ok(game) => game,
+        Err(err) => {
+            tracing::error!(%err, "failed to dump game");
+            return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+        }
+    };
+
+    Ok(Json(json!({
+        "room_id": room_info.room,
+        "settings": room_info.settings,
+        "game": game,
+        "white": room_info.white,
+        "move_deadline": room_info.move_deadline,
+    }))
+    .into_response())
+}
+
+#[derive(Deserialize)]
+struct PostMoveRequest {
+    player_id: String,
+    /// Sent to the player in a `seat` message when they first joined the game.
+    seat_token: String,
+    password: Option<String>,
+    #[serde(flatten)]
+    move_data: MoveData,
+}
+
+/// Plays a move in a correspondence game without holding a websocket open.
+async fn post_move(
+    Path(room_id): Path<String>,
+    Extension(state): Extension<State>,
+    Json(request): Json<PostMoveRequest>,
+) -> Result<Response, Response> {
+    if state.shutting_down.load(Ordering::SeqCst) {
+        return Err(JoinRejection::ShuttingDown.into_response());
+    }
+    validate_player_id(&request.player_id).map_err(IntoResponse::into_response)?;
+    let handle = state
+        .registry
+        .get(&room_id)
+        .ok_or(RoomError::RoomNotFound.into_response())?;
+    let password_checked = password_checked(&handle, request.password.as_deref()).await;
+    handle
+        .play_move(
+            &request.player_id,
+            &request.seat_token,
+            password_checked,
+            request.move_data,
+        )
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    let move_deadline = handle.info().await.ok().and_then(|room_info| room_info.move_deadline);
+    Ok(Json(json!({ "room_id": room_id, "move_deadline": move_deadline })).into_response())
+}
+
+/// Whether `password` opens the room behind `handle`, worked out before the room is asked to act
+/// on it.
+async fn password_checked(handle: &RoomHandle, password: Option<&str>) -> bool {
+    match handle.info().await {
+        Ok(room_info) => room_info.access.verify_password(password).await,
+        Err(_) => false,
+    }
+}
+
+#[derive(Deserialize)]
+struct BotParams {
+    level: u8,
+    password: Option<String>,
+}
+
+/// Seats a bot as the room's second player. It moves through the room like a connected player,
+/// so the human opposite sees its moves arrive like anyone else's.
+async fn add_bot(
+    Path(room_id): Path<String>,
+    Query(params): Query<BotParams>,
+    Extension(state): Extension<State>,
+) -> Result<Response, Response> {
+    if state.shutting_down.load(Ordering::SeqCst) {
+        return Err(JoinRejection::ShuttingDown.into_response());
+    }
+    validate_bot_level(params.level).map_err(IntoResponse::into_response)?;
+    let handle = state
+        .registry
+        .get(&room_id)
+        .ok_or(RoomError::RoomNotFound.into_response())?;
+    let password_checked = password_checked(&handle, params.password.as_deref()).await;
+    bot::seat(handle, &room_id, params.level, password_checked)
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(json!({ "room_id": room_id, "player_id": bot::BOT_ID, "level": params.level })),
+    )
+        .into_response())
+}
+
+#[derive(Deserialize)]
+struct JoinParams {
+    initial_secs: Option<u64>,
+    increment_secs: Option<u64>,
+    password: Option<String>,
+}
+
+/// Joins, or opens, a room named in the URL, with the rule set and time control given there.
+async fn websocket_handler(
+    ws: WebSocketUpgrade,
+    Path((room, id, rule_set)): Path<(String, String, String)>,
+    Query(params): Query<JoinParams>,
+    headers: HeaderMap,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    Extension(state): Extension<State>,
+) -> Result<Response, JoinRejection> {
+    validate_rule_set(&rule_set)?;
+    let time_control = validate_time_control(params.initial_secs, params.increment_secs)?;
+    let request = JoinRequest {
+        room,
+        id,
+        rule_set,
+        time_control,
+        password_checked: false,
+        existing_only: false,
+    };
+    upgrade(ws, state, request, params.password, headers, connect_info).await
+}
+
+/// Joins a room created through `POST /rooms`, which brings its own settings.
+async fn join_room_handler(
+    ws: WebSocketUpgrade,
+    Path((room, id)): Path<(String, String)>,
+    Query(params): Query<JoinParams>,
+    headers: HeaderMap,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    Extension(state): Extension<State>,
+) -> Result<Response, JoinRejection> {
+    validate_room_id(&room)?;
+    let handle = state.registry.get(&room).ok_or(JoinRejection::RoomNotFound)?;
+    let request = JoinRequest {
+        rule_set: handle.rule_set().to_string(),
+        room,
+        id,
+        time_control: None,
+        password_checked: false,
+        existing_only: true,
+    };
+    upgrade(ws, state, request, params.password, headers, connect_info).await
+}
+
+/// Checks the room password here, once, so the room itself never has to.
+async fn upgrade(
+    ws: WebSocketUpgrade,
+    state: State,
+    mut request: JoinRequest,
+    password: Option<String>,
+    headers: HeaderMap,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+) -> Result<Response, JoinRejection> {
+    if state.shutting_down.load(Ordering::SeqCst) {
+        return Err(JoinRejection::ShuttingDown);
+    }
+    if !state.cors.allows_origin(headers.get(header::ORIGIN)) {
+        return Err(JoinRejection::OriginNotAllowed);
+    }
+    validate_room_id(&request.room)?;
+    validate_player_id(&request.id)?;
+
+    if let Some(handle) = state.registry.get(&request.room) {
+        let room_info = handle.info().await;
+        if let Ok(room_info) = &room_info {
+            if !room_info.access.verify_password(password.as_deref()).await {
+                return Err(JoinRejection::WrongPassword);
+            }
+            request.password_checked = true;
+        }
+        if handle.rule_set() != request.rule_set {
+            return Err(JoinRejection::RuleSetMismatch {
+                room_rule_set: handle.rule_set().to_string(),
+            });
+        }
+        if room_info.is_ok_and(|room_info| room_info.players.contains(&request.id)) {
+            return Err(JoinRejection::DuplicatePlayerId);
+        }
+    }
+
+    let ip = client_ip(&headers, connect_info, &state.trusted_proxies);
+    let limits = state.limits;
+    let limiter = ConnectionLimiter::new(limits, ip, state.ip_limiter.clone());
+
+    Ok(ws
+        .max_message_size(limits.protocol_max_bytes())
+        .max_frame_size(limits.protocol_max_bytes())
+        .on_upgrade(move |socket| {
+            let span = tracing::info_span!(
+                "connection",
+                room = %request.room,
+                player_id = %request.id,
+                rule_set = %request.rule_set,
+                ?ip
+            );
+            websocket(socket, state, request, limiter).instrument(span)
+        }))
+}
+
+/// The peer address, unless the peer is a trusted proxy, in which case the client is the last
+/// `X-Forwarded-For` entry not added by another trusted proxy. Anyone else could put whatever they
+/// like in the header, so it is ignored. Without a known peer there is no address to limit by.
+fn client_ip(
+    headers: &HeaderMap,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    trusted_proxies: &[IpAddr],
+) -> Option<IpAddr> {
+    let peer = connect_info.map(|ConnectInfo(addr)| addr.ip())?;
+    if !trusted_proxies.contains(&peer) {
+        return Some(peer);
+    }
+
+    let forwarded = headers
+        .get("x-forwarded-for")
+        .and_then(|value| value.to_str().ok())
+        .into_iter()
+        .flat_map(|value| value.rsplit(','))
+        .map(|entry| entry.trim().parse::<IpAddr>().ok())
+        .find(|entry| !entry.is_some_and(|ip| trusted_proxies.contains(&ip)));
+    match forwarded {
+        Some(Some(ip)) => Some(ip),
+        // a garbled entry can't be attributed to anyone, so it counts against the proxy
+        Some(None) | None => Some(peer),
+    }
+}
+
+/// A validated join, carried from the upgrade request into the connection.
+struct JoinRequest {
+    room: String,
+    id: String,
+    rule_set: String,
+    time_control: Option<TimeControl>,
+    /// The room was running at upgrade and the password matched. Rooms opened by the join itself
+    /// have no password.
+    password_checked: bool,
+    /// Only join a running room, never open one.
+    existing_only: bool,
+}
+
+async fn websocket(stream: WebSocket, state: State, request: JoinRequest, mut limiter: ConnectionLimiter) {
+    let JoinRequest {
+        room,
+        id,
+        rule_set,
+        time_control,
+        password_checked,
+        existing_only,
+    } = request;
+    let (mut sender, mut receiver) = stream.split();
+
+    let joined = if existing_only {
+        state.registry.join_existing(&room, &id, password_checked).await
+    } else {
+        state
+            .registry
+            .join(&room, &id, &rule_set, time_control, password_checked)
+            .await
+    };
+    let (handle, mut outbox) = match joined {
+        Ok(joined) => joined,
+        Err(err) => {
+            tracing::info!(%err, "join rejected");
+            let cancel_connection_msg = sys_message(&id, "error", &err.to_string());
+            if sender.send(Message::Text(cancel_connection_msg)).await.is_err() {
+                tracing::warn!("failed to send join rejection");
+            }
+            return;
+        }
+    };
+    state.registry.metrics().connections.inc();
+
+    let (close_tx, mut close_rx) = mpsc::channel::<CloseFrame<'static>>(1);
+
+    // Ping payloads carry the milliseconds since `connected` at which they were sent, so a pong
+    // tells both that the peer is alive and how long the round trip took.
+    let connected = Instant::now();
+    let last_ping_ms = Arc::new(AtomicU64::new(0));
+    let last_pong_ms = Arc::new(AtomicU64::new(0));
+    let mut ping_interval = interval_at(tokio::time::Instant::now() + state.ws_ping, state.ws_ping);
+    ping_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let pong_timeout = state.ws_pong_timeout;
+
+    let mut global_rx = state.global_rx.clone();
+    let shutting_down = state.shutting_down.clone();
+    let send_ping_ms = last_ping_ms.clone();
+    let send_pong_ms = last_pong_ms.clone();
+    let mut send_task = tokio::spawn(
+        async move {
+            loop {
+                tokio::select! {
+                    _ = ping_interval.tick() => {
+                        let now_ms = connected.elapsed().as_millis() as u64;
+                        let silent = Duration::from_millis(now_ms.saturating_sub(send_pong_ms.load(Ordering::Relaxed)));
+                        if silent > pong_timeout {
+                            tracing::info!(silent_secs = silent.as_secs(), "peer stopped answering pings");
+                            return;
+                        }
+                        send_ping_ms.store(now_ms, Ordering::Relaxed);
+                        if sender.send(Message::Ping(now_ms.to_be_bytes().to_vec())).await.is_err() {
+                            return;
+                        }
+                    },
+                    Some(frame) = close_rx.recv() => {
+                        if sender.send(Message::Close(Some(frame))).await.is_err() {
+                            tracing::warn!("failed to send close frame");
+                        }
+                        return;
+                    },
+                    _ = global_rx.changed() => {
+                        let msg = global_rx.borrow().clone();
+                        if sender.send(msg).await.is_err() {
+                            return;
+                        }
+                    },
+                    msg = outbox.recv() => {
+                        // the room dropped us or shut down
+                        let Some(msg) = msg else {
+                            if shutting_down.load(Ordering::SeqCst) {
+                                let frame = CloseFrame {
+                                    code: close_code::RESTART,
+                                    reason: "Server restarting".into(),
+                                };
+                                if sender.send(Message::Close(Some(frame))).await.is_err() {
+                                    tracing::warn!("failed to send close frame");
+                                }
+                            }
+                            return;
+                        };
+                        if sender.send(msg).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+        .in_current_span(),
+    );
+
+    let recv_handle = handle.clone();
+    let recv_id = id.clone();
+    let mut recv_task = tokio::spawn(
+        async move {
+            while let Some(Ok(message)) = receiver.next().await {
+                let text = match message {
+                    Message::Text(text) => text,
+                    Message::Pong(payload) => {
+                        // only the answer to the latest ping counts, so stray pongs can't flood the room
+                        let Ok(sent_ms) = payload.try_into().map(u64::from_be_bytes) else {
+                            continue;
+                        };
+                        if sent_ms != last_ping_ms.load(Ordering::Relaxed)
+                            || last_pong_ms.swap(sent_ms, Ordering::Relaxed) == sent_ms
+                        {
+                            continue;
+                        }
+                        let rtt = connected.elapsed().saturating_sub(Duration::from_millis(sent_ms));
+                        tracing::debug!(rtt_ms = rtt.as_millis() as u64, "pong");
+                        if recv_handle.report_latency(&recv_id, rtt).await.is_err() {
+                            break;
+                        }
+                        continue;
+                    }
+                    Message::Ping(_) => continue,
+                    Message::Binary(_) | Message::Close(_) => break,
+                };
+
+                if let Err(violation) = limiter.check(&text) {
+                    tracing::warn!(?violation, "closing connection");
+                    return Some(violation);
+                }
+
+                if let Err(err) = parse_frame(&text, &recv_id) {
+                    tracing::info!(%err, "invalid message");
+                    continue;
+                }
+
+                tracing::debug!(%text, "relaying message");
+
+                if recv_handle.relay(&recv_id, text).await.is_err() {
+                    break;
+                }
+            }
+            None
+        }
+        .in_current_span(),
+    );
+
+    tokio::select! {
+        _ = (&mut send_task) => recv_task.abort(),
+        violation = (&mut recv_task) => {
+            match violation {
+                Ok(Some(violation)) => {
+                    // let the send task deliver the close frame before it shuts down
+                    if close_tx.send(violation.close_frame()).await.is_err() || send_task.await.is_err() {
+                        tracing::warn!("failed to close connection");
+                    }
+                }
+                _ => send_task.abort(),
+            }
+        },
+    };
+
+    state.registry.metrics().connections.dec();
+    match handle.leave(&id).await {
+        Ok(Departure::Left | Departure::RoomRemoved | Departure::Parked) => {}
+        Err(err) => tracing::warn!(%err, "leave failed"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(forwarded: &str, peer: &str, trusted_proxies: &[IpAddr]) -> Option<IpAddr> {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", forwarded.parse().unwrap());
+        let peer = SocketAddr::new(peer.parse().unwrap(), 443);
+        client_ip(&headers, Some(ConnectInfo(peer)), trusted_proxies)
+    }
+
+    #[test]
+    fn forwarded_for_is_only_believed_from_trusted_proxies() {
+        let proxy: IpAddr = "10.0.0.1".parse().unwrap();
+        let ip = |ip: &str| Some(ip.parse::<IpAddr>().unwrap());
+
+        assert_eq!(client("198.51.100.7", "203.0.113.9", &[]), ip("203.0.113.9"));
+        assert_eq!(client("198.51.100.7", "203.0.113.9", &[proxy]), ip("203.0.113.9"));
+        assert_eq!(client("198.51.100.7", "10.0.0.1", &[proxy]), ip("198.51.100.7"));
+        // a client can prepend anything, but the proxy appends the address it saw
+        assert_eq!(
+            client("1.2.3.4, 198.51.100.7", "10.0.0.1", &[proxy]),
+            ip("198.51.100.7")
+        );
+        assert_eq!(
+            client("198.51.100.7, 10.0.0.1", "10.0.0.1", &[proxy]),
+            ip("198.51.100.7")
+        );
+        assert_eq!(client("garbage", "10.0.0.1", &[proxy]), ip("10.0.0.1"));
+        assert_eq!(client_ip(&HeaderMap::new(), None, &[proxy]), None);
+    }
+}
@@ -0,0 +1,269 @@
+use axum::extract::ws::{close_code, CloseFrame};
+use clap::Args;
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+const MAX_FRAME_BYTES: usize = 16 * 1024;
+const CONN_BURST: f64 = 20.0;
+const CONN_PER_SEC: f64 = 5.0;
+const IP_BURST: f64 = 60.0;
+const IP_PER_SEC: f64 = 20.0;
+
+#[derive(Debug, Clone, Copy, Args)]
+pub struct Limits {
+    /// Largest text frame a client may send, in bytes
+    #[arg(long, env = "MAX_FRAME_BYTES", default_value_t = MAX_FRAME_BYTES)]
+    pub max_frame_bytes: usize,
+
+    /// Messages a connection may send in a burst
+    #[arg(long, env = "CONN_BURST", default_value_t = CONN_BURST, value_parser = parse_burst)]
+    pub conn_burst: f64,
+
+    /// Messages per second a connection may sustain
+    #[arg(long, env = "CONN_PER_SEC", default_value_t = CONN_PER_SEC, value_parser = parse_rate)]
+    pub conn_per_sec: f64,
+
+    /// Messages all connections from one address may send in a burst
+    #[arg(long, env = "IP_BURST", default_value_t = IP_BURST, value_parser = parse_burst)]
+    pub ip_burst: f64,
+
+    /// Messages per second all connections from one address may sustain
+    #[arg(long, env = "IP_PER_SEC", default_value_t = IP_PER_SEC, value_parser = parse_rate)]
+    pub ip_per_sec: f64,
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            max_frame_bytes: MAX_FRAME_BYTES,
+            conn_burst: CONN_BURST,
+            conn_per_sec: CONN_PER_SEC,
+            ip_burst: IP_BURST,
+            ip_per_sec: IP_PER_SEC,
+        }
+    }
+}
+
+impl Limits {
+    /// Hard cap handed to the websocket protocol layer. Frames between `max_frame_bytes` and this
+    /// are read so the client can be closed with a proper code; anything larger is dropped outright.
+    pub fn protocol_max_bytes(&self) -> usize {
+        self.max_frame_bytes * 2
+    }
+}
+
+/// A burst below one message would never let anything through.
+fn parse_burst(burst: &str) -> Result<f64, String> {
+    match burst.trim().parse::<f64>() {
+        Ok(burst) if burst.is_finite() && burst >= 1.0 => Ok(burst),
+        _ => Err(format!("burst must be a number of messages, at least 1, not {}", burst)),
+    }
+}
+
+fn parse_rate(rate: &str) -> Result<f64, String> {
+    match rate.trim().parse::<f64>() {
+        Ok(rate) if rate.is_finite() && rate > 0.0 => Ok(rate),
+        _ => Err(format!(
+            "rate must be a positive number of messages per second, not {}",
+            rate
+        )),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    capacity: f64,
+    refill_per_sec: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(capacity: f64, refill_per_sec: f64) -> TokenBucket {
+        TokenBucket {
+            capacity,
+            refill_per_sec,
+            tokens: capacity,
+            last_refill: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        self.last_refill = now;
+    }
+
+    pub fn try_take(&mut self) -> bool {
+        self.refill();
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+
+    pub fn is_full(&mut self) -> bool {
+        self.refill();
+        self.tokens >= self.capacity
+    }
+}
+
+/// Token buckets shared by every connection coming from the same address.
+#[derive(Debug, Clone, Default)]
+pub struct IpRateLimiter {
+    buckets: Arc<Mutex<HashMap<IpAddr, TokenBucket>>>,
+}
+
+impl IpRateLimiter {
+    pub fn try_take(&self, ip: IpAddr, limits: &Limits) -> bool {
+        let mut buckets = self.buckets.lock().unwrap();
+        buckets
+            .entry(ip)
+            .or_insert_with(|| TokenBucket::new(limits.ip_burst, limits.ip_per_sec))
+            .try_take()
+    }
+
+    /// Forget addresses whose bucket has refilled completely, so the map doesn't grow forever.
+    pub fn prune(&self) {
+        self.buckets.lock().unwrap().retain(|_, bucket| !bucket.is_full());
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Violation {
+    FrameTooLarge,
+    RateLimited,
+}
+
+impl Violation {
+    pub fn close_frame(&self) -> CloseFrame<'static> {
+        match self {
+            Violation::FrameTooLarge => CloseFrame {
+                code: close_code::SIZE,
+                reason: "Message too large".into(),
+            },
+            Violation::RateLimited => CloseFrame {
+                code: close_code::POLICY,
+                reason: "Rate limit exceeded".into(),
+            },
+        }
+    }
+}
+
+/// Per-connection limiter; checks frame size, then the connection bucket, then the address bucket.
+pub struct ConnectionLimiter {
+    limits: Limits,
+    bucket: TokenBucket,
+    ip: Option<IpAddr>,
+    ip_limiter: IpRateLimiter,
+}
+
+impl ConnectionLimiter {
+    pub fn new(limits: Limits, ip: Option<IpAddr>, ip_limiter: IpRateLimiter) -> ConnectionLimiter {
+        ConnectionLimiter {
+            limits,
+            bucket: TokenBucket::new(limits.conn_burst, limits.conn_per_sec),
+            ip,
+            ip_limiter,
+        }
+    }
+
+    pub fn check(&mut self, text: &str) -> Result<(), Violation> {
+        if text.len() > self.limits.max_frame_bytes {
+            return Err(Violation::FrameTooLarge);
+        }
+        if !self.bucket.try_take() {
+            return Err(Violation::RateLimited);
+        }
+        if let Some(ip) = self.ip {
+            if !self.ip_limiter.try_take(ip, &self.limits) {
+                return Err(Violation::RateLimited);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn drain(bucket: &mut TokenBucket) -> usize {
+        std::iter::from_fn(|| bucket.try_take().then_some(()))
+            .take(1000)
+            .count()
+    }
+
+    #[test]
+    fn bucket_allows_a_burst_then_refills_at_its_rate() {
+        let mut bucket = TokenBucket::new(3.0, 2.0);
+        assert_eq!(drain(&mut bucket), 3);
+
+        bucket.last_refill -= Duration::from_secs(1);
+        assert_eq!(drain(&mut bucket), 2);
+
+        // refilling stops at the burst size however long the bucket sits
+        bucket.last_refill -= Duration::from_secs(60);
+        assert!(bucket.is_full());
+        assert_eq!(drain(&mut bucket), 3);
+    }
+
+    #[test]
+    fn connection_limiter_checks_size_then_both_buckets() {
+        let limits = Limits {
+            max_frame_bytes: 8,
+            conn_burst: 2.0,
+            ip_burst: 3.0,
+            ..Limits::default()
+        };
+        let ip = Some("192.0.2.1".parse().unwrap());
+        let ip_limiter = IpRateLimiter::default();
+        let mut first = ConnectionLimiter::new(limits, ip, ip_limiter.clone());
+        let mut second = ConnectionLimiter::new(limits, ip, ip_limiter);
+
+        assert_eq!(first.check("too long!"), Err(Violation::FrameTooLarge));
+        assert_eq!(first.check("hi"), Ok(()));
+        assert_eq!(first.check("hi"), Ok(()));
+        assert_eq!(first.check("hi"), Err(Violation::RateLimited));
+        // the second connection has its own bucket but shares the address's
+        assert_eq!(second.check("hi"), Ok(()));
+        assert_eq!(second.check("hi"), Err(Violation::RateLimited));
+    }
+
+    #[test]
+    fn prune_evicts_only_refilled_addresses() {
+        let limits = Limits::default();
+        let ip_limiter = IpRateLimiter::default();
+        let busy: IpAddr = "192.0.2.1".parse().unwrap();
+        let idle: IpAddr = "192.0.2.2".parse().unwrap();
+        for _ in 0..10 {
+            assert!(ip_limiter.try_take(busy, &limits));
+        }
+        assert!(ip_limiter.try_take(idle, &limits));
+        ip_limiter.buckets.lock().unwrap().get_mut(&idle).unwrap().last_refill -= Duration::from_secs(60);
+
+        ip_limiter.prune();
+        let buckets = ip_limiter.buckets.lock().unwrap();
+        assert!(buckets.contains_key(&busy));
+        assert!(!buckets.contains_key(&idle));
+    }
+
+    #[test]
+    fn rates_and_bursts_must_be_positive() {
+        assert_eq!(parse_rate("2.5"), Ok(2.5));
+        assert!(parse_rate("0").is_err());
+        assert!(parse_rate("-1").is_err());
+        assert!(parse_rate("inf").is_err());
+        assert_eq!(parse_burst("1"), Ok(1.0));
+        assert!(parse_burst("0.5").is_err());
+        assert!(parse_burst("-3").is_err());
+        assert!(parse_burst("lots").is_err());
+    }
+}
@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+/// Envelope for everything sent over a room socket. `data` is itself a JSON string so the server
+/// can relay player messages without understanding them.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct WsMessage {
+    pub sender_id: String,
+    pub data: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct SysMessage {
+    pub message_type: String,
+    pub text: String,
+}
+
+/// Serialized `WsMessage` carrying a `SysMessage`, ready to go out on a socket.
+pub fn sys_message(sender_id: &str, message_type: &str, text: &str) -> String {
+    json!(WsMessage {
+        sender_id: sender_id.to_string(),
+        data: json!(SysMessage {
+            message_type: message_type.to_string(),
+            text: text.to_string()
+        })
+        .to_string(),
+    })
+    .to_string()
+}
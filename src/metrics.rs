@@ -0,0 +1,97 @@
+use prometheus::{
+    core::Collector, Encoder, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts,
+    TextEncoder,
+};
+
+/// Message types counted under their own label; anything else a client sends is counted as `other`
+/// so clients can't blow up the number of series.
+const MESSAGE_TYPES: [&str; 8] = ["move", "join", "init", "reset", "resign", "draw", "leave", "ping"];
+
+/// Relay latency buckets in seconds, from well under a millisecond up to a badly backed up room.
+const LATENCY_BUCKETS: [f64; 10] = [0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.1, 0.5];
+
+/// Prometheus metrics for the server. Cloning is cheap; every clone updates the same series.
+#[derive(Debug, Clone)]
+pub struct Metrics {
+    registry: prometheus::Registry,
+    pub connections: IntGauge,
+    pub rooms: IntGaugeVec,
+    pub games_started: IntCounterVec,
+    pub games_finished: IntCounterVec,
+    pub messages_relayed: IntCounterVec,
+    pub rejected_moves: IntCounterVec,
+    pub reconnects: IntCounter,
+    pub relay_latency: Histogram,
+}
+
+impl Default for Metrics {
+    fn default() -> Metrics {
+        let metrics = Metrics {
+            registry: prometheus::Registry::new(),
+            connections: IntGauge::new("chess_active_connections", "Open websocket connections").unwrap(),
+            rooms: IntGaugeVec::new(Opts::new("chess_active_rooms", "Running rooms"), &["rule_set"]).unwrap(),
+            games_started: IntCounterVec::new(
+                Opts::new("chess_games_started_total", "Games that had their first move played"),
+                &["rule_set"],
+            )
+            .unwrap(),
+            games_finished: IntCounterVec::new(
+                Opts::new("chess_games_finished_total", "Games that ended, by how they ended"),
+                &["result"],
+            )
+            .unwrap(),
+            messages_relayed: IntCounterVec::new(
+                Opts::new("chess_messages_relayed_total", "Player messages accepted by a room"),
+                &["message_type"],
+            )
+            .unwrap(),
+            rejected_moves: IntCounterVec::new(
+                Opts::new("chess_rejected_moves_total", "Moves refused by a room"),
+                &["reason"],
+            )
+            .unwrap(),
+            reconnects: IntCounter::new("chess_reconnects_total", "Players returning to a game in progress").unwrap(),
+            relay_latency: Histogram::with_opts(
+                HistogramOpts::new(
+                    "chess_relay_latency_seconds",
+                    "Time from receiving a player's message to handing it to the other players",
+                )
+                .buckets(LATENCY_BUCKETS.to_vec()),
+            )
+            .unwrap(),
+        };
+
+        let collectors: [Box<dyn Collector>; 8] = [
+            Box::new(metrics.connections.clone()),
+            Box::new(metrics.rooms.clone()),
+            Box::new(metrics.games_started.clone()),
+            Box::new(metrics.games_finished.clone()),
+            Box::new(metrics.messages_relayed.clone()),
+            Box::new(metrics.rejected_moves.clone()),
+            Box::new(metrics.reconnects.clone()),
+            Box::new(metrics.relay_latency.clone()),
+        ];
+        for collector in collectors {
+            metrics.registry.register(collector).unwrap();
+        }
+        metrics
+    }
+}
+
+impl Metrics {
+    pub fn relayed(&self, message_type: &str) {
+        let label = if MESSAGE_TYPES.contains(&message_type) {
+            message_type
+        } else {
+            "other"
+        };
+        self.messages_relayed.with_label_values(&[label]).inc();
+    }
+
+    /// All series in the Prometheus text exposition format.
+    pub fn render(&self) -> Result<String, prometheus::Error> {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(String::from_utf8_lossy(&buffer).into_owned())
+    }
+}
@@ -0,0 +1,27 @@
+use crate::{registry::Registry, room::RoomTtls};
+use futures::future::join_all;
+use std::time::Duration;
+use tokio::time::{interval, MissedTickBehavior};
+use tracing::Instrument;
+
+/// Sweeps the registry every `every`, closing rooms that outlived their TTL. Each room decides for
+/// itself, so the sweep never holds the registry lock while rooms are busy.
+pub fn spawn(registry: Registry, ttls: RoomTtls, every: Duration) {
+    let reaper = async move {
+        let mut ticker = interval(every);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            let handles = registry.handles();
+            let expired = join_all(handles.iter().map(|handle| handle.expire(ttls)))
+                .await
+                .into_iter()
+                .filter(|expired| *expired == Ok(true))
+                .count();
+            if expired > 0 {
+                tracing::info!(expired, "reaped rooms");
+            }
+        }
+    };
+    tokio::spawn(reaper.instrument(tracing::info_span!("reaper")));
+}
@@ -0,0 +1,192 @@
+use crate::{
+    access::{self, RoomAccess},
+    clock::TimeControl,
+    metrics::Metrics,
+    room::{self, RoomError, RoomHandle, RoomSnapshot},
+    settings::RoomSettings,
+    storage::GameWriter,
+};
+use axum::extract::ws::Message;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+use tokio::sync::mpsc;
+
+/// Attempts at joining a room that shuts down between lookup and join before giving up.
+const JOIN_ATTEMPTS: usize = 3;
+
+/// Maps room ids to running room actors. The lock is only held to look up, insert or remove a
+/// handle, never while talking to a room.
+#[derive(Debug, Clone, Default)]
+pub struct Registry {
+    rooms: Arc<Mutex<HashMap<String, RoomHandle>>>,
+    next_generation: Arc<AtomicU64>,
+    metrics: Metrics,
+    /// Where correspondence games are saved; without one they only survive a graceful restart.
+    games: Option<GameWriter>,
+}
+
+impl Registry {
+    pub fn with_games(games: GameWriter) -> Registry {
+        Registry {
+            games: Some(games),
+            ..Registry::default()
+        }
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    pub fn get(&self, room: &str) -> Option<RoomHandle> {
+        self.rooms.lock().unwrap().get(room).cloned()
+    }
+
+    pub fn room_count(&self) -> usize {
+        self.rooms.lock().unwrap().len()
+    }
+
+    pub fn handles(&self) -> Vec<RoomHandle> {
+        self.rooms.lock().unwrap().values().cloned().collect()
+    }
+
+    pub(crate) fn save_game(&self, snapshot: RoomSnapshot) {
+        if let Some(games) = &self.games {
+            games.save(snapshot);
+        }
+    }
+
+    pub(crate) fn forget_game(&self, room: &str) {
+        if let Some(games) = &self.games {
+            games.remove(room);
+        }
+    }
+
+    fn next_generation(&self) -> u64 {
+        self.next_generation.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Restarts a room from a snapshot, unless a room with that id is already running.
+    pub fn restore(&self, snapshot: RoomSnapshot) {
+        let mut rooms = self.rooms.lock().unwrap();
+        if rooms.contains_key(&snapshot.room) {
+            tracing::warn!(room = %snapshot.room, "not restoring room: already running");
+            return;
+        }
+
+        let room = snapshot.room.clone();
+        let handle = room::restore(self.clone(), snapshot, self.next_generation());
+        rooms.insert(room, handle);
+    }
+
+    fn get_or_spawn(&self, room: &str, settings: &RoomSettings) -> RoomHandle {
+        let mut rooms = self.rooms.lock().unwrap();
+        if let Some(handle) = rooms.get(room) {
+            return handle.clone();
+        }
+
+        let handle = room::spawn(
+            self.clone(),
+            room,
+            settings.clone(),
+            RoomAccess::default(),
+            self.next_generation(),
+        );
+        rooms.insert(room.to_string(), handle.clone());
+        handle
+    }
+
+    /// Starts a room under a fresh invite code and returns the code. The room waits for its
+    /// players like any other and expires if nobody turns up.
+    pub fn create(&self, settings: RoomSettings, access: RoomAccess) -> String {
+        let mut rooms = self.rooms.lock().unwrap();
+        let mut room = access::invite_code();
+        while rooms.contains_key(&room) {
+            room = access::invite_code();
+        }
+
+        let handle = room::spawn(self.clone(), &room, settings, access, self.next_generation());
+        rooms.insert(room.clone(), handle);
+        room
+    }
+
+    /// Seats `id` in `room`, starting an open room if it isn't running. `rule_set` and
+    /// `time_control` only apply to a new room; joining a running one requires a matching rule set,
+    /// and `password_checked` if the room has a password.
+    pub async fn join(
+        &self,
+        room: &str,
+        id: &str,
+        rule_set: &str,
+        time_control: Option<TimeControl>,
+        password_checked: bool,
+    ) -> Result<(RoomHandle, mpsc::Receiver<Message>), RoomError> {
+        let settings = RoomSettings::implicit(rule_set, time_control);
+        self.join_room(room, id, Some(&settings), password_checked).await
+    }
+
+    /// Seats `id` in a running room, taking whatever settings it was created with.
+    pub async fn join_existing(
+        &self,
+        room: &str,
+        id: &str,
+        password_checked: bool,
+    ) -> Result<(RoomHandle, mpsc::Receiver<Message>), RoomError> {
+        self.join_room(room, id, None, password_checked).await
+    }
+
+    async fn join_room(
+        &self,
+        room: &str,
+        id: &str,
+        new_room: Option<&RoomSettings>,
+        password_checked: bool,
+    ) -> Result<(RoomHandle, mpsc::Receiver<Message>), RoomError> {
+        for _ in 0..JOIN_ATTEMPTS {
+            let handle = match new_room {
+                Some(settings) => self.get_or_spawn(room, settings),
+                None => self.get(room).ok_or(RoomError::RoomNotFound)?,
+            };
+            if new_room.is_some_and(|settings| handle.rule_set() != settings.rule_set) {
+                return Err(RoomError::RuleSetMismatch {
+                    room_rule_set: handle.rule_set().to_string(),
+                });
+            }
+
+            match handle.join(id, password_checked).await {
+                Ok(outbox) => return Ok((handle, outbox)),
+                // the room shut down after we looked it up; the next attempt starts a fresh one
+                Err(RoomError::RoomNotFound) => continue,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Err(RoomError::RoomNotFound)
+    }
+
+    /// Called by an empty room before shutting down. Refuses if commands are still queued (someone
+    /// is about to join) so the room keeps running for them.
+    pub(crate) fn remove_if_idle<T>(&self, room: &str, generation: u64, cmd_rx: &mpsc::Receiver<T>) -> bool {
+        let mut rooms = self.rooms.lock().unwrap();
+        if !cmd_rx.is_empty() {
+            return false;
+        }
+        remove_generation(&mut rooms, room, generation);
+        true
+    }
+
+    /// Forgets `room` if it is still the given generation, for a room that is closing itself.
+    pub(crate) fn remove(&self, room: &str, generation: u64) {
+        remove_generation(&mut self.rooms.lock().unwrap(), room, generation);
+    }
+}
+
+fn remove_generation(rooms: &mut HashMap<String, RoomHandle>, room: &str, generation: u64) {
+    if rooms.get(room).is_some_and(|handle| handle.generation() == generation) {
+        rooms.remove(room);
+    }
+}
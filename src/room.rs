@@ -0,0 +1,1492 @@
+use crate::{
+    access::RoomAccess,
+    bot::BOT_ID,
+    clock::{Clock, ClockSnapshot, Side},
+    message::{sys_message, WsMessage},
+    registry::Registry,
+    settings::{ColourPreference, RoomSettings},
+};
+use axum::{
+    extract::ws::Message,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chess::Game;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{
+    fmt::{Display, Formatter},
+    time::{Duration, Instant},
+};
+use tokio::{
+    sync::{mpsc, oneshot},
+    time::sleep_until,
+};
+use tracing::Instrument;
+
+const MAX_PLAYERS: usize = 2;
+const COMMAND_QUEUE_SIZE: usize = 64;
+const OUTBOX_SIZE: usize = 32;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RoomError {
+    RoomFull,
+    DuplicatePlayer,
+    SeatReserved,
+    RuleSetMismatch { room_rule_set: String },
+    RoomNotFound,
+    PlayerNotInRoom,
+    MalformedMessage(String),
+    SenderMismatch { sender_id: String },
+    IllegalMove(String),
+    NotYourTurn,
+    OutOfTime,
+    WrongPassword,
+    NotInvited,
+    WrongSeatToken,
+    WaitingForOpponent,
+    NotCorrespondence,
+    NobodyToPlay,
+}
+
+impl RoomError {
+    /// Label for the rejected moves metric, if this error refused a move.
+    pub fn rejected_move_reason(&self) -> Option<&'static str> {
+        match self {
+            RoomError::IllegalMove(_) => Some("illegal"),
+            RoomError::NotYourTurn | RoomError::WaitingForOpponent => Some("not_your_turn"),
+            RoomError::OutOfTime => Some("out_of_time"),
+            _ => None,
+        }
+    }
+}
+
+impl Display for RoomError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            RoomError::RoomFull => write!(f, "Game is full"),
+            RoomError::DuplicatePlayer => write!(f, "Player id already in room"),
+            RoomError::SeatReserved => write!(f, "Seats are reserved for the players of a restored game"),
+            RoomError::RuleSetMismatch { room_rule_set } => write!(f, "Room is playing rule set: {}", room_rule_set),
+            RoomError::RoomNotFound => write!(f, "Room does not exist"),
+            RoomError::PlayerNotInRoom => write!(f, "Player is not in room"),
+            RoomError::MalformedMessage(reason) => write!(f, "Malformed message: {}", reason),
+            RoomError::SenderMismatch { sender_id } => write!(f, "Message sent on behalf of {}", sender_id),
+            RoomError::IllegalMove(reason) => write!(f, "Illegal move: {}", reason),
+            RoomError::NotYourTurn => write!(f, "Not your turn"),
+            RoomError::OutOfTime => write!(f, "Out of time"),
+            RoomError::WrongPassword => write!(f, "Wrong room password"),
+            RoomError::NotInvited => write!(f, "The last seat is reserved for an invited player"),
+            RoomError::WrongSeatToken => write!(f, "Seat token does not match"),
+            RoomError::WaitingForOpponent => write!(f, "Waiting for an opponent"),
+            RoomError::NotCorrespondence => write!(f, "Moves can only be posted to correspondence games"),
+            RoomError::NobodyToPlay => write!(f, "Bots only join rooms with a player waiting"),
+        }
+    }
+}
+
+impl IntoResponse for RoomError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            RoomError::RoomNotFound => StatusCode::NOT_FOUND,
+            RoomError::PlayerNotInRoom
+            | RoomError::WrongPassword
+            | RoomError::NotInvited
+            | RoomError::WrongSeatToken => StatusCode::FORBIDDEN,
+            RoomError::MalformedMessage(_) | RoomError::SenderMismatch { .. } | RoomError::IllegalMove(_) => {
+                StatusCode::BAD_REQUEST
+            }
+            RoomError::RoomFull
+            | RoomError::DuplicatePlayer
+            | RoomError::SeatReserved
+            | RoomError::RuleSetMismatch { .. }
+            | RoomError::NotYourTurn
+            | RoomError::OutOfTime
+            | RoomError::WaitingForOpponent
+            | RoomError::NotCorrespondence
+            | RoomError::NobodyToPlay => StatusCode::CONFLICT,
+        };
+
+        (status, Json(json!({ "error": self.to_string() }))).into_response()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Departure {
+    /// Other players are still in the room and have been told.
+    Left,
+    /// The last player left and the room shut down.
+    RoomRemoved,
+    /// The last player left, but the room holds seats for a restored or correspondence game and
+    /// keeps running until its players return or it expires.
+    Parked,
+}
+
+/// How long a room may sit without progress before the reaper closes it.
+#[derive(Debug, Clone, Copy)]
+pub struct RoomTtls {
+    /// For rooms still waiting for a second player.
+    pub waiting: Duration,
+    /// For full rooms where nobody has moved.
+    pub idle_game: Duration,
+}
+
+#[derive(Clone)]
+pub struct RoomInfo {
+    pub room: String,
+    pub players: Vec<String>,
+    pub settings: RoomSettings,
+    pub access: RoomAccess,
+    pub game: Game,
+    pub white: Option<String>,
+    pub move_deadline: Option<DateTime<Utc>>,
+}
+
+enum RoomCommand {
+    Join {
+        id: String,
+        password_checked: bool,
+        reply: oneshot::Sender<Result<mpsc::Receiver<Message>, RoomError>>,
+    },
+    Leave {
+        id: String,
+        reply: oneshot::Sender<Result<Departure, RoomError>>,
+    },
+    Relay {
+        id: String,
+        text: String,
+        received: Instant,
+    },
+    Move {
+        id: String,
+        seat_token: String,
+        password_checked: bool,
+        move_data: MoveData,
+        reply: oneshot::Sender<Result<(), RoomError>>,
+    },
+    Info {
+        reply: oneshot::Sender<RoomInfo>,
+    },
+    Latency {
+        id: String,
+        rtt: Duration,
+    },
+    Shutdown {
+        reply: oneshot::Sender<RoomSnapshot>,
+    },
+    Expire {
+        ttls: RoomTtls,
+        reply: oneshot::Sender<bool>,
+    },
+}
+
+/// Everything needed to bring a room back after a restart.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RoomSnapshot {
+    pub room: String,
+    #[serde(flatten)]
+    pub settings: RoomSettings,
+    pub clock: Option<ClockSnapshot>,
+    pub game: Game,
+    pub white: Option<String>,
+    pub players: Vec<String>,
+    #[serde(default)]
+    pub access: RoomAccess,
+    #[serde(default)]
+    pub move_deadline: Option<DateTime<Utc>>,
+}
+
+/// Cheap, cloneable address of a running room. Every method fails with `RoomNotFound` once the
+/// room has shut down.
+#[derive(Debug, Clone)]
+pub struct RoomHandle {
+    cmd_tx: mpsc::Sender<RoomCommand>,
+    rule_set: String,
+    generation: u64,
+}
+
+impl RoomHandle {
+    pub fn rule_set(&self) -> &str {
+        &self.rule_set
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    async fn request<T>(&self, command: impl FnOnce(oneshot::Sender<T>) -> RoomCommand) -> Result<T, RoomError> {
+        let (reply, response) = oneshot::channel();
+        self.cmd_tx
+            .send(command(reply))
+            .await
+            .map_err(|_| RoomError::RoomNotFound)?;
+        response.await.map_err(|_| RoomError::RoomNotFound)
+    }
+
+    /// Takes a seat and returns the player's outbox, which closes when they are removed.
+    /// `password_checked` says the caller has verified the room password, which the room won't
+    /// hash itself so that joins can't stall it.
+    pub async fn join(&self, id: &str, password_checked: bool) -> Result<mpsc::Receiver<Message>, RoomError> {
+        let id = id.to_string();
+        self.request(|reply| RoomCommand::Join {
+            id,
+            password_checked,
+            reply,
+        })
+        .await?
+    }
+
+    pub async fn leave(&self, id: &str) -> Result<Departure, RoomError> {
+        let id = id.to_string();
+        self.request(|reply| RoomCommand::Leave { id, reply }).await?
+    }
+
+    /// Hands a validated frame from `id` to the room; rule violations are reported back to the
+    /// sender through their outbox.
+    pub async fn relay(&self, id: &str, text: String) -> Result<(), RoomError> {
+        self.cmd_tx
+            .send(RoomCommand::Relay {
+                id: id.to_string(),
+                text,
+                received: Instant::now(),
+            })
+            .await
+            .map_err(|_| RoomError::RoomNotFound)
+    }
+
+    /// Plays a move for a seated player of a correspondence game, connected or not, who proves
+    /// the seat is theirs with the token they were given on taking it.
+    pub async fn play_move(
+        &self,
+        id: &str,
+        seat_token: &str,
+        password_checked: bool,
+        move_data: MoveData,
+    ) -> Result<(), RoomError> {
+        let id = id.to_string();
+        let seat_token = seat_token.to_string();
+        self.request(|reply| RoomCommand::Move {
+            id,
+            seat_token,
+            password_checked,
+            move_data,
+            reply,
+        })
+        .await?
+    }
+
+    /// Shares a player's measured round trip time with everyone in the room, them included.
+    pub async fn report_latency(&self, id: &str, rtt: Duration) -> Result<(), RoomError> {
+        self.cmd_tx
+            .send(RoomCommand::Latency {
+                id: id.to_string(),
+                rtt,
+            })
+            .await
+            .map_err(|_| RoomError::RoomNotFound)
+    }
+
+    pub async fn info(&self) -> Result<RoomInfo, RoomError> {
+        self.request(|reply| RoomCommand::Info { reply }).await
+    }
+
+    /// Closes the room if it has gone without progress for longer than its TTL, adjudicating a
+    /// clocked game in progress. Returns whether the room closed.
+    pub async fn expire(&self, ttls: RoomTtls) -> Result<bool, RoomError> {
+        self.request(|reply| RoomCommand::Expire { ttls, reply }).await
+    }
+
+    /// Tells the players the server is restarting, then stops the room and returns its state.
+    pub async fn shutdown(&self) -> Result<RoomSnapshot, RoomError> {
+        self.request(|reply| RoomCommand::Shutdown { reply }).await
+    }
+}
+
+struct Player {
+    id: String,
+    outbox: mpsc::Sender<Message>,
+}
+
+/// A room's game, clock and subscribers, owned by a single task. Players talk to it through a
+/// `RoomHandle`; the registry only knows the handle.
+struct RoomActor {
+    room: String,
+    settings: RoomSettings,
+    generation: u64,
+    players: Vec<Player>,
+    /// Ids allowed back into a restored game, or the seats of a correspondence game; otherwise
+    /// empty once the game is over or for a fresh room.
+    reserved: Vec<String>,
+    white: Option<String>,
+    game: Game,
+    clock: Option<Clock>,
+    access: RoomAccess,
+    cmd_rx: mpsc::Receiver<RoomCommand>,
+    registry: Registry,
+    closed: bool,
+    /// Last join or move, for expiry.
+    last_activity: Instant,
+    /// When the side to move in a correspondence game loses by default.
+    move_deadline: Option<DateTime<Utc>>,
+    /// Set when a game ends and cleared by the next game's first move, so a late resignation or
+    /// draw acceptance can't end the same game twice.
+    game_over: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MoveData {
+    pub start_sq_coords: String,
+    pub end_sq_coords: String,
+    #[serde(default, rename = "lastPawnAction")]
+    pub last_pawn_action: String,
+}
+
+pub fn spawn(
+    registry: Registry,
+    room: &str,
+    settings: RoomSettings,
+    access: RoomAccess,
+    generation: u64,
+) -> RoomHandle {
+    let (cmd_tx, cmd_rx) = mpsc::channel(COMMAND_QUEUE_SIZE);
+    tracing::info!(room, ?settings, "creating room");
+    let actor = RoomActor {
+        room: room.to_string(),
+        generation,
+        players: Vec::new(),
+        reserved: Vec::new(),
+        white: None,
+        game: Game::init(settings.rule_set.clone()).expect("rule sets are validated before rooms are created"),
+        clock: settings.time_control.map(Clock::new),
+        settings,
+        access,
+        cmd_rx,
+        registry,
+        closed: false,
+        last_activity: Instant::now(),
+        move_deadline: None,
+        game_over: false,
+    };
+    if actor.settings.correspondence.is_some() {
+        actor.registry.save_game(actor.snapshot());
+    }
+    start(actor, cmd_tx)
+}
+
+/// Brings back a room saved by `RoomHandle::shutdown`. A game that had both its players holds
+/// their seats for them; a room that was still waiting for an opponent takes anyone again.
+pub fn restore(registry: Registry, snapshot: RoomSnapshot, generation: u64) -> RoomHandle {
+    let (cmd_tx, cmd_rx) = mpsc::channel(COMMAND_QUEUE_SIZE);
+    // correspondence seats are held from the moment they are taken, full or not
+    let reserved = if snapshot.settings.correspondence.is_some() || snapshot.players.len() >= MAX_PLAYERS {
+        snapshot.players
+    } else {
+        Vec::new()
+    };
+    let clock = match (snapshot.settings.time_control, snapshot.clock) {
+        (Some(time_control), Some(clock)) => Some(Clock::restore(time_control, clock)),
+        (Some(time_control), None) => Some(Clock::new(time_control)),
+        _ => None,
+    };
+    let actor = RoomActor {
+        room: snapshot.room,
+        settings: snapshot.settings,
+        generation,
+        players: Vec::new(),
+        reserved,
+        white: snapshot.white,
+        game: snapshot.game,
+        clock,
+        access: snapshot.access,
+        cmd_rx,
+        registry,
+        closed: false,
+        last_activity: Instant::now(),
+        move_deadline: snapshot.move_deadline,
+        game_over: false,
+    };
+    tracing::info!(room = %actor.room, rule_set = %actor.settings.rule_set, "restoring room");
+    start(actor, cmd_tx)
+}
+
+fn start(actor: RoomActor, cmd_tx: mpsc::Sender<RoomCommand>) -> RoomHandle {
+    let handle = RoomHandle {
+        cmd_tx,
+        rule_set: actor.settings.rule_set.clone(),
+        generation: actor.generation,
+    };
+    let rule_set = &actor.settings.rule_set;
+    actor.registry.metrics().rooms.with_label_values(&[rule_set]).inc();
+    let span = tracing::info_span!(parent: None, "room", room = %actor.room, %rule_set);
+    tokio::spawn(actor.run().instrument(span));
+    handle
+}
+
+impl RoomActor {
+    async fn run(mut self) {
+        loop {
+            let deadline = self.clock.as_ref().and_then(|clock| clock.deadline());
+            tokio::select! {
+                command = self.cmd_rx.recv() => {
+                    let Some(command) = command else {
+                        break;
+                    };
+                    // only a departure empties a room; a room created ahead of its players waits
+                    // for them until the reaper steps in
+                    let departure = matches!(command, RoomCommand::Leave { .. });
+                    self.handle(command);
+                    if self.closed {
+                        break;
+                    }
+                    if departure
+                        && self.players.is_empty()
+                        && self.reserved.is_empty()
+                        && self.registry.remove_if_idle(&self.room, self.generation, &self.cmd_rx) {
+                        break;
+                    }
+                },
+                _ = sleep_until(deadline.unwrap_or_else(tokio::time::Instant::now)), if deadline.is_some() => {
+                    self.check_flag();
+                },
+            }
+        }
+        self.registry
+            .metrics()
+            .rooms
+            .with_label_values(&[&self.settings.rule_set])
+            .dec();
+        tracing::info!("deleting room");
+    }
+
+    fn handle(&mut self, command: RoomCommand) {
+        match command {
+            RoomCommand::Join {
+                id,
+                password_checked,
+                reply,
+            } => {
+                let result = self.join(id.clone(), password_checked);
+                let seated = result.is_ok();
+                if reply.send(result).is_err() && seated {
+                    // the joiner went away before getting its seat
+                    let _ = self.leave(&id);
+                } else if seated {
+                    self.send_settings(&id);
+                }
+            }
+            RoomCommand::Leave { id, reply } => {
+                let _ = reply.send(self.leave(&id));
+            }
+            RoomCommand::Relay { id, text, received } => match self.relay(&id, text) {
+                Ok(()) => self
+                    .registry
+                    .metrics()
+                    .relay_latency
+                    .observe(received.elapsed().as_secs_f64()),
+                Err(err) => {
+                    tracing::warn!(player_id = %id, %err, "rejected message");
+                    self.count_rejection(&err);
+                    self.send_to(&id, sys_message("server", "error", &err.to_string()));
+                }
+            },
+            RoomCommand::Move {
+                id,
+                seat_token,
+                password_checked,
+                move_data,
+                reply,
+            } => {
+                let result = self.play_posted_move(&id, &seat_token, password_checked, &move_data);
+                if let Err(err) = &result {
+                    tracing::warn!(player_id = %id, %err, "rejected posted move");
+                    self.count_rejection(err);
+                }
+                let _ = reply.send(result);
+            }
+            RoomCommand::Shutdown { reply } => {
+                self.broadcast_except(
+                    "",
+                    sys_message("server", "server_restart", "Server restarting, reconnect to continue"),
+                );
+                let _ = reply.send(self.snapshot());
+                self.closed = true;
+            }
+            RoomCommand::Expire { ttls, reply } => {
+                let _ = reply.send(self.expire(ttls));
+            }
+            RoomCommand::Latency { id, rtt } => {
+                let data = json!({
+                    "message_type": "latency",
+                    "player_id": id,
+                    "rtt_ms": rtt.as_millis() as u64,
+                });
+                self.broadcast_except("", server_message(data));
+            }
+            RoomCommand::Info { reply } => {
+                let _ = reply.send(RoomInfo {
+                    room: self.room.clone(),
+                    players: self.players.iter().map(|player| player.id.clone()).collect(),
+                    settings: self.settings.clone(),
+                    access: self.access.clone(),
+                    game: self.game,
+                    white: self.white.clone(),
+                    move_deadline: self.move_deadline,
+                });
+            }
+        }
+    }
+
+    fn join(&mut self, id: String, password_checked: bool) -> Result<mpsc::Receiver<Message>, RoomError> {
+        if self.access.is_password_protected() && !password_checked {
+            return Err(RoomError::WrongPassword);
+        }
+        if self.players.iter().any(|player| player.id == id) {
+            return Err(RoomError::DuplicatePlayer);
+        }
+        if self.players.len() >= MAX_PLAYERS {
+            return Err(RoomError::RoomFull);
+        }
+        if self.seat_held_for_others(&id) {
+            return Err(RoomError::SeatReserved);
+        }
+        if let Some(invited) = &self.access.invited_player {
+            let seats = self.seats();
+            let last_seat = !seats.contains(&id) && seats.len() + 1 == MAX_PLAYERS;
+            if last_seat && id != *invited && !seats.contains(invited) {
+                return Err(RoomError::NotInvited);
+            }
+        }
+
+        if self.reserved.contains(&id) || self.white.as_ref() == Some(&id) {
+            self.registry.metrics().reconnects.inc();
+        }
+
+        let (outbox, outbox_rx) = mpsc::channel(OUTBOX_SIZE);
+        tracing::info!(player_id = %id, "player joined");
+        self.last_activity = Instant::now();
+        let seat_token = self.take_seat(&id);
+        self.players.push(Player { id: id.clone(), outbox });
+        if let Some(seat_token) = seat_token {
+            let data = json!({ "message_type": "seat", "seat_token": seat_token });
+            self.send_to(&id, server_message(data));
+        }
+
+        if self.players.len() == MAX_PLAYERS {
+            if let Some(clock) = self.clock.as_mut() {
+                clock.resume();
+            }
+        }
+        Ok(outbox_rx)
+    }
+
+    /// Players of a correspondence game, connected or not; everyone connected for other rooms.
+    fn seats(&self) -> Vec<String> {
+        if self.settings.correspondence.is_some() {
+            return self.reserved.clone();
+        }
+        self.players.iter().map(|player| player.id.clone()).collect()
+    }
+
+    /// Restored games hold every seat for their players. Correspondence games fill their seats as
+    /// players first join and hold them from then on.
+    fn seat_held_for_others(&self, id: &str) -> bool {
+        let held = if self.settings.correspondence.is_some() {
+            self.reserved.len() >= MAX_PLAYERS
+        } else {
+            !self.reserved.is_empty()
+        };
+        held && !self.reserved.iter().any(|seat| seat == id)
+    }
+
+    /// Correspondence seats outlast the connection that took them, and come with a token for
+    /// posting moves to them, which is returned to pass on to the player. White's first move falls
+    /// due once both are taken.
+    fn take_seat(&mut self, id: &str) -> Option<String> {
+        let correspondence = self.settings.correspondence?;
+        if self.reserved.iter().any(|seat| seat == id) {
+            return None;
+        }
+        self.reserved.push(id.to_string());
+        let seat_token = self.access.issue_seat_token(id);
+        if self.reserved.len() == MAX_PLAYERS {
+            self.move_deadline = Some(Utc::now() + correspondence.per_move());
+        }
+        self.registry.save_game(self.snapshot());
+        Some(seat_token)
+    }
+
+    /// Tells a new player how the room is set up, then settles colours if the room is now full.
+    /// Players returning to a correspondence game also get the position to carry on from.
+    fn send_settings(&mut self, id: &str) {
+        let data = json!({
+            "message_type": "settings",
+            "room_id": self.room,
+            "settings": self.settings,
+        });
+        self.send_to(id, server_message(data));
+
+        match self.white.clone() {
+            Some(white) => self.send_colour(id, &white),
+            None => self.assign_colours(),
+        }
+
+        if self.settings.correspondence.is_some() {
+            match self.game.dump() {
+                Ok(game) => {
+                    let data = json!({
+                        "message_type": "position",
+                        "game": game,
+                        "move_deadline": self.move_deadline,
+                    });
+                    self.send_to(id, server_message(data));
+                }
+                Err(err) => tracing::error!(%err, "failed to dump game"),
+            }
+        }
+    }
+
+    /// Once both seats are taken, colours follow the creator's (first player's) preference.
+    /// Rooms without a preference leave colours to the clients, unless one seat is a bot's, which
+    /// can't negotiate and is given a random colour instead.
+    fn assign_colours(&mut self) {
+        if self.white.is_some() {
+            return;
+        }
+        let seats = self.seats();
+        let [creator, opponent] = &seats[..] else {
+            return;
+        };
+        let preference = match self.settings.colour {
+            Some(preference) => preference,
+            None if seats.iter().any(|id| id == BOT_ID) => ColourPreference::Random.resolve(),
+            None => return,
+        };
+        let white = match preference {
+            ColourPreference::Black => opponent.clone(),
+            ColourPreference::White | ColourPreference::Random => creator.clone(),
+        };
+        self.white = Some(white.clone());
+        for id in &seats {
+            self.send_colour(id, &white);
+        }
+        if self.settings.correspondence.is_some() {
+            self.registry.save_game(self.snapshot());
+        }
+    }
+
+    fn send_colour(&mut self, id: &str, white: &str) {
+        let colour = if id == white { Side::White } else { Side::Black };
+        let data = json!({ "message_type": "colour", "colour": colour, "white": white });
+        self.send_to(id, server_message(data));
+    }
+
+    fn snapshot(&self) -> RoomSnapshot {
+        let mut players: Vec<String> = self.players.iter().map(|player| player.id.clone()).collect();
+        for id in &self.reserved {
+            if !players.contains(id) {
+                players.push(id.clone());
+            }
+        }
+
+        RoomSnapshot {
+            room: self.room.clone(),
+            settings: self.settings.clone(),
+            clock: self.clock.as_ref().map(|clock| clock.snapshot()),
+            game: self.game,
+            white: self.white.clone(),
+            players,
+            access: self.access.clone(),
+            move_deadline: self.move_deadline,
+        }
+    }
+
+    fn leave(&mut self, id: &str) -> Result<Departure, RoomError> {
+        let seat = self
+            .players
+            .iter()
+            .position(|player| player.id == id)
+            .ok_or(RoomError::PlayerNotInRoom)?;
+        self.players.remove(seat);
+        tracing::info!(player_id = %id, "player left");
+
+        if self.players.is_empty() {
+            if !self.reserved.is_empty() {
+                return Ok(Departure::Parked);
+            }
+            return Ok(Departure::RoomRemoved);
+        }
+
+        self.broadcast_except(id, sys_message(id, "leave", "Disconnected"));
+        Ok(Departure::Left)
+    }
+
+    fn relay(&mut self, id: &str, text: String) -> Result<(), RoomError> {
+        if !self.players.iter().any(|player| player.id == id) {
+            return Err(RoomError::PlayerNotInRoom);
+        }
+        let ws_message = parse_frame(&text, id)?;
+        let data: serde_json::Value =
+            serde_json::from_str(&ws_message.data).map_err(|err| RoomError::MalformedMessage(err.to_string()))?;
+
+        let message_type = data["message_type"]
+            .as_str()
+            .ok_or_else(|| RoomError::MalformedMessage("missing message_type".to_string()))?
+            .to_string();
+        let ends_game = message_type == "resign" || (message_type == "draw" && data["type"] == "accept");
+        if ends_game && self.game_over {
+            return Ok(());
+        }
+        match message_type.as_str() {
+            "move" => {
+                let move_data: MoveData =
+                    serde_json::from_value(data).map_err(|err| RoomError::MalformedMessage(err.to_string()))?;
+                self.play_move(id, &move_data)?;
+                self.broadcast_except(id, text);
+                self.after_move();
+            }
+            "resign" => {
+                self.finish_game("resign");
+                self.broadcast_except(id, text);
+            }
+            "draw" if data["type"] == "accept" => {
+                self.finish_game("draw");
+                self.broadcast_except(id, text);
+            }
+            _ => self.broadcast_except(id, text),
+        }
+
+        self.registry.metrics().relayed(&message_type);
+        Ok(())
+    }
+
+    /// A move posted without a connection, by a seated player of a correspondence game. Anyone
+    /// connected to the room sees it like any other move.
+    fn play_posted_move(
+        &mut self,
+        id: &str,
+        seat_token: &str,
+        password_checked: bool,
+        move_data: &MoveData,
+    ) -> Result<(), RoomError> {
+        if self.settings.correspondence.is_none() {
+            return Err(RoomError::NotCorrespondence);
+        }
+        if self.access.is_password_protected() && !password_checked {
+            return Err(RoomError::WrongPassword);
+        }
+        if !self.reserved.iter().any(|seat| seat == id) {
+            return Err(RoomError::PlayerNotInRoom);
+        }
+        if !self.access.check_seat_token(id, seat_token) {
+            return Err(RoomError::WrongSeatToken);
+        }
+
+        self.play_move(id, move_data)?;
+        let data = json!({
+            "message_type": "move",
+            "start_sq_coords": move_data.start_sq_coords,
+            "end_sq_coords": move_data.end_sq_coords,
+            "lastPawnAction": move_data.last_pawn_action,
+        });
+        let text = json!(WsMessage {
+            sender_id: id.to_string(),
+            data: data.to_string(),
+        })
+        .to_string();
+        self.broadcast_except(id, text);
+        self.after_move();
+        self.registry.metrics().relayed("move");
+        Ok(())
+    }
+
+    /// Unless the room assigns colours, they are negotiated between the clients and the server
+    /// learns them from whoever plays the first white move.
+    fn play_move(&mut self, id: &str, move_data: &MoveData) -> Result<(), RoomError> {
+        if self.settings.colour.is_some() && self.white.is_none() {
+            return Err(RoomError::WaitingForOpponent);
+        }
+        let side = if self.game.is_white_turn() {
+            Side::White
+        } else {
+            Side::Black
+        };
+        match (&self.white, side) {
+            (None, Side::Black) => return Err(RoomError::NotYourTurn),
+            (Some(white), Side::White) if white != id => return Err(RoomError::NotYourTurn),
+            (Some(white), Side::Black) if white == id => return Err(RoomError::NotYourTurn),
+            _ => {}
+        }
+        if self.clock.as_ref().and_then(|clock| clock.flagged()) == Some(side) {
+            self.flag_fell(side);
+            return Err(RoomError::OutOfTime);
+        }
+        // the reaper adjudicates missed deadlines on its next sweep
+        if self.move_deadline.is_some_and(|deadline| Utc::now() >= deadline) {
+            return Err(RoomError::OutOfTime);
+        }
+
+        let promotion = Some(move_data.last_pawn_action.as_str()).filter(|piece| !piece.is_empty());
+        self.game
+            .try_move(&move_data.start_sq_coords, &move_data.end_sq_coords, promotion)
+            .map_err(|err| RoomError::IllegalMove(err.to_string()))?;
+
+        if self.white.is_none() {
+            self.white = Some(id.to_string());
+        }
+        if self.game.move_num() == 1 {
+            self.game_over = false;
+            self.registry
+                .metrics()
+                .games_started
+                .with_label_values(&[&self.settings.rule_set])
+                .inc();
+        }
+        if let Some(clock) = self.clock.as_mut() {
+            clock.press(side);
+        }
+        self.last_activity = Instant::now();
+        if let Some(correspondence) = self.settings.correspondence {
+            self.move_deadline = Some(Utc::now() + correspondence.per_move());
+            self.registry.save_game(self.snapshot());
+        }
+        Ok(())
+    }
+
+    fn expire(&mut self, ttls: RoomTtls) -> bool {
+        const WAITING: &str = "Room closed after waiting too long for an opponent";
+        let waiting = self.seats().len() < MAX_PLAYERS;
+        let idle = self.last_activity.elapsed();
+        let (expired, reason) = match (self.move_deadline, self.settings.correspondence) {
+            (Some(deadline), _) => (Utc::now() >= deadline, "Room closed because the move deadline passed"),
+            // a correspondence game waits as long for an opponent as it would for a move
+            (None, Some(correspondence)) => (idle >= correspondence.per_move(), WAITING),
+            (None, None) if waiting => (idle >= ttls.waiting, WAITING),
+            (None, None) => (idle >= ttls.idle_game, "Room closed because the game went idle"),
+        };
+        if !expired {
+            return false;
+        }
+        tracing::info!(idle_secs = idle.as_secs(), waiting, "room expired");
+
+        // a game in progress is decided rather than dropped when there's a clock or deadline to
+        // go by
+        if self.move_deadline.is_some() || self.game.move_num() > 0 {
+            if self.clock.is_some() || self.move_deadline.is_some() {
+                let loser = if self.game.is_white_turn() {
+                    Side::White
+                } else {
+                    Side::Black
+                };
+                let data = json!({ "message_type": "adjudicated", "loser": loser, "text": reason });
+                self.broadcast_except("", server_message(data));
+                self.finish_game("adjudicated");
+            } else {
+                self.finish_game("abandoned");
+            }
+        }
+
+        let data = json!({ "message_type": "expired", "text": reason });
+        self.broadcast_except("", server_message(data));
+        self.registry.forget_game(&self.room);
+        self.registry.remove(&self.room, self.generation);
+        self.closed = true;
+        true
+    }
+
+    fn after_move(&mut self) {
+        let side = if self.game.is_white_turn() { "White" } else { "Black" };
+        // `in_checkmate` only asks whether the side to move is out of moves; being in check as
+        // well decides between a win and a draw
+        let result = self.game.in_checkmate(side).and_then(|stuck| {
+            if !stuck {
+                return Ok(None);
+            }
+            Ok(Some(if self.game.in_check(side)? {
+                "checkmate"
+            } else {
+                "stalemate"
+            }))
+        });
+        match result {
+            Ok(Some(result)) => {
+                tracing::info!(side, result, "game over");
+                self.finish_game(result);
+                return;
+            }
+            Ok(None) => {}
+            Err(err) => tracing::error!(%err, "failed to check for checkmate"),
+        }
+        self.broadcast_clock();
+    }
+
+    fn check_flag(&mut self) {
+        if let Some(side) = self.clock.as_ref().and_then(|clock| clock.flagged()) {
+            self.flag_fell(side);
+        }
+    }
+
+    fn flag_fell(&mut self, side: Side) {
+        tracing::info!(?side, "flag fell");
+        let data = json!({ "message_type": "timeout", "loser": side });
+        self.broadcast_except("", server_message(data));
+        self.finish_game("timeout");
+    }
+
+    fn broadcast_clock(&mut self) {
+        let Some(clock) = self.clock.as_ref() else {
+            return;
+        };
+        let data = json!({
+            "message_type": "clock",
+            "white_ms": clock.remaining(Side::White).as_millis() as u64,
+            "black_ms": clock.remaining(Side::Black).as_millis() as u64,
+        });
+        self.broadcast_except("", server_message(data));
+    }
+
+    fn finish_game(&mut self, result: &str) {
+        self.game_over = true;
+        self.registry
+            .metrics()
+            .games_finished
+            .with_label_values(&[result])
+            .inc();
+        if self.settings.correspondence.is_some() {
+            // a correspondence room holds a single game and closes once it is decided
+            self.registry.forget_game(&self.room);
+            self.registry.remove(&self.room, self.generation);
+            self.closed = true;
+            return;
+        }
+        self.new_game();
+    }
+
+    fn count_rejection(&self, err: &RoomError) {
+        if let Some(reason) = err.rejected_move_reason() {
+            self.registry
+                .metrics()
+                .rejected_moves
+                .with_label_values(&[reason])
+                .inc();
+        }
+    }
+
+    fn new_game(&mut self) {
+        self.game.reset();
+        if self.settings.colour.is_none() {
+            self.white = None;
+        }
+        self.reserved.clear();
+        if let Some(clock) = self.clock.as_mut() {
+            clock.reset();
+        }
+        // a bot's colours are settled afresh for each game
+        self.assign_colours();
+    }
+
+    fn send_to(&mut self, id: &str, text: String) {
+        self.deliver(|player| player.id == id, text);
+    }
+
+    fn broadcast_except(&mut self, id: &str, text: String) {
+        self.deliver(|player| player.id != id, text);
+    }
+
+    /// Players whose outbox is full or gone are dropped; their connection notices the closed
+    /// outbox and leaves on its own.
+    fn deliver(&mut self, to: impl Fn(&Player) -> bool, text: String) {
+        self.players.retain(|player| {
+            if !to(player) {
+                return true;
+            }
+            match player.outbox.try_send(Message::Text(text.clone())) {
+                Ok(()) => true,
+                Err(err) => {
+                    tracing::warn!(player_id = %player.id, %err, "dropping player");
+                    false
+                }
+            }
+        });
+    }
+}
+
+fn server_message(data: serde_json::Value) -> String {
+    json!(WsMessage {
+        sender_id: "server".to_string(),
+        data: data.to_string(),
+    })
+    .to_string()
+}
+
+/// Parses a frame received from player `id`, rejecting anything that isn't a `WsMessage` from them.
+pub fn parse_frame(text: &str, id: &str) -> Result<WsMessage, RoomError> {
+    let ws_message: WsMessage =
+        serde_json::from_str(text).map_err(|err| RoomError::MalformedMessage(err.to_string()))?;
+
+    if ws_message.sender_id != id {
+        return Err(RoomError::SenderMismatch {
+            sender_id: ws_message.sender_id,
+        });
+    }
+
+    Ok(ws_message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{Correspondence, TimeControl};
+
+    fn frame(id: &str, data: serde_json::Value) -> String {
+        json!({ "sender_id": id, "data": data.to_string() }).to_string()
+    }
+
+    fn move_frame(id: &str, start: &str, end: &str) -> String {
+        frame(
+            id,
+            json!({ "message_type": "move", "start_sq_coords": start, "end_sq_coords": end, "lastPawnAction": "" }),
+        )
+    }
+
+    /// Joins `room` and consumes the settings message every joiner gets first.
+    async fn seat(registry: &Registry, id: &str) -> (RoomHandle, mpsc::Receiver<Message>) {
+        let (handle, mut outbox) = registry.join("room", id, "standard", None, false).await.unwrap();
+        let Some(Message::Text(text)) = outbox.recv().await else {
+            panic!("expected text");
+        };
+        assert!(text.contains("settings"), "{} should be the room settings", text);
+        (handle, outbox)
+    }
+
+    #[tokio::test]
+    async fn third_player_is_rejected() {
+        let registry = Registry::default();
+        assert!(registry.join("room", "a", "standard", None, false).await.is_ok());
+        assert!(registry.join("room", "b", "standard", None, false).await.is_ok());
+
+        assert_eq!(
+            registry.join("room", "c", "standard", None, false).await.err(),
+            Some(RoomError::RoomFull)
+        );
+    }
+
+    #[tokio::test]
+    async fn duplicate_and_mismatched_joins_are_rejected() {
+        let registry = Registry::default();
+        let (handle, _outbox) = registry.join("room", "a", "standard", None, false).await.unwrap();
+
+        assert_eq!(
+            registry.join("room", "a", "standard", None, false).await.err(),
+            Some(RoomError::DuplicatePlayer)
+        );
+        assert_eq!(
+            registry.join("room", "b", "shuffled", None, false).await.err(),
+            Some(RoomError::RuleSetMismatch {
+                room_rule_set: "standard".to_string()
+            })
+        );
+        assert_eq!(handle.info().await.unwrap().players, vec!["a".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn double_leave_is_an_error_not_a_panic() {
+        let registry = Registry::default();
+        let (handle, _a) = registry.join("room", "a", "standard", None, false).await.unwrap();
+        let (_, _b) = registry.join("room", "b", "standard", None, false).await.unwrap();
+
+        assert_eq!(handle.leave("a").await, Ok(Departure::Left));
+        assert_eq!(handle.leave("a").await, Err(RoomError::PlayerNotInRoom));
+
+        assert_eq!(handle.leave("b").await, Ok(Departure::RoomRemoved));
+        assert_eq!(handle.leave("b").await, Err(RoomError::RoomNotFound));
+        assert!(registry.get("room").is_none());
+    }
+
+    #[tokio::test]
+    async fn concurrent_leaves_only_count_once() {
+        let registry = Registry::default();
+        let (handle, _outbox) = registry.join("room", "a", "standard", None, false).await.unwrap();
+
+        let leaves: Vec<_> = (0..8)
+            .map(|_| {
+                let handle = handle.clone();
+                tokio::spawn(async move { handle.leave("a").await })
+            })
+            .collect();
+
+        let mut successes = 0;
+        for leave in leaves {
+            match leave.await.unwrap() {
+                Ok(departure) => {
+                    assert_eq!(departure, Departure::RoomRemoved);
+                    successes += 1;
+                }
+                Err(err) => assert!(err == RoomError::RoomNotFound || err == RoomError::PlayerNotInRoom),
+            }
+        }
+
+        assert_eq!(successes, 1);
+        assert!(registry.get("room").is_none());
+    }
+
+    #[tokio::test]
+    async fn room_is_recreated_after_shutdown() {
+        let registry = Registry::default();
+        let (handle, _outbox) = registry.join("room", "a", "standard", None, false).await.unwrap();
+        handle.leave("a").await.unwrap();
+
+        let (new_handle, _outbox) = registry.join("room", "a", "shuffled", None, false).await.unwrap();
+        assert_ne!(new_handle.generation(), handle.generation());
+        assert_eq!(new_handle.rule_set(), "shuffled");
+    }
+
+    #[tokio::test]
+    async fn restored_room_keeps_game_and_seats() {
+        let registry = Registry::default();
+        let (handle, _a) = registry.join("room", "a", "standard", None, false).await.unwrap();
+        let (_, _b) = registry.join("room", "b", "standard", None, false).await.unwrap();
+        handle.relay("a", move_frame("a", "e2", "e4")).await.unwrap();
+
+        let snapshot = handle.shutdown().await.unwrap();
+        assert_eq!(snapshot.players, vec!["a".to_string(), "b".to_string()]);
+        assert_eq!(snapshot.white, Some("a".to_string()));
+
+        let registry = Registry::default();
+        registry.restore(snapshot);
+        assert_eq!(
+            registry.join("room", "c", "standard", None, false).await.err(),
+            Some(RoomError::SeatReserved)
+        );
+        let (handle, mut a) = seat(&registry, "a").await;
+        let (_, mut b) = seat(&registry, "b").await;
+        // white is already known, so each joiner is told their colour straight away
+        for outbox in [&mut a, &mut b] {
+            let Message::Text(text) = outbox.recv().await.unwrap() else {
+                panic!("expected text");
+            };
+            assert!(
+                text.contains(r#"\"white\":\"a\""#),
+                "{} should name the white player",
+                text
+            );
+        }
+
+        handle.relay("b", move_frame("b", "e7", "e5")).await.unwrap();
+        let Message::Text(text) = a.recv().await.unwrap() else {
+            panic!("expected text");
+        };
+        assert_eq!(text, move_frame("b", "e7", "e5"));
+        assert!(b.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn restored_waiting_room_takes_a_new_opponent() {
+        let registry = Registry::default();
+        let (handle, _a) = registry.join("room", "a", "standard", None, false).await.unwrap();
+        let snapshot = handle.shutdown().await.unwrap();
+        assert_eq!(snapshot.players, vec!["a".to_string()]);
+
+        let registry = Registry::default();
+        registry.restore(snapshot);
+        let (_, _b) = seat(&registry, "b").await;
+        let (handle, _a) = seat(&registry, "a").await;
+        assert_eq!(
+            handle.info().await.unwrap().players,
+            vec!["b".to_string(), "a".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn repeated_resignations_finish_one_game() {
+        let registry = Registry::default();
+        let (handle, _a) = registry.join("room", "a", "standard", None, false).await.unwrap();
+        let (_, mut b) = seat(&registry, "b").await;
+        let resign = frame("a", json!({ "message_type": "resign" }));
+        let accept = frame("a", json!({ "message_type": "draw", "type": "accept" }));
+        handle.relay("a", move_frame("a", "e2", "e4")).await.unwrap();
+        for text in [resign.clone(), resign, accept] {
+            handle.relay("a", text).await.unwrap();
+        }
+        handle.info().await.unwrap();
+        let finished = |result: &str| registry.metrics().games_finished.with_label_values(&[result]).get();
+        assert_eq!((finished("resign"), finished("draw")), (1, 0));
+
+        let mut resignations = 0;
+        while let Ok(Message::Text(text)) = b.try_recv() {
+            resignations += text.contains("resign") as usize;
+        }
+        assert_eq!(resignations, 1);
+
+        handle.relay("a", move_frame("a", "e2", "e4")).await.unwrap();
+        handle
+            .relay("a", frame("a", json!({ "message_type": "draw", "type": "accept" })))
+            .await
+            .unwrap();
+        handle.info().await.unwrap();
+        assert_eq!(finished("draw"), 1);
+    }
+
+    #[tokio::test]
+    async fn checkmate_and_stalemate_are_counted_apart() {
+        let registry = Registry::default();
+        let (handle, _a) = registry.join("room", "a", "standard", None, false).await.unwrap();
+        let (_, _b) = registry.join("room", "b", "standard", None, false).await.unwrap();
+        let play = |moves: &'static [(&'static str, &'static str)]| {
+            let handle = handle.clone();
+            async move {
+                for (i, (start, end)) in moves.iter().enumerate() {
+                    let id = if i % 2 == 0 { "a" } else { "b" };
+                    handle.relay(id, move_frame(id, start, end)).await.unwrap();
+                }
+            }
+        };
+        let finished = |result: &str| registry.metrics().games_finished.with_label_values(&[result]).get();
+
+        play(&[("f2", "f3"), ("e7", "e5"), ("g2", "g4"), ("d8", "h4")]).await;
+        handle.info().await.unwrap();
+        assert_eq!((finished("checkmate"), finished("stalemate")), (1, 0));
+
+        // Loyd's ten move stalemate
+        play(&[
+            ("e2", "e3"),
+            ("a7", "a5"),
+            ("d1", "h5"),
+            ("a8", "a6"),
+            ("h5", "a5"),
+            ("h7", "h5"),
+            ("h2", "h4"),
+            ("a6", "h6"),
+            ("a5", "c7"),
+            ("f7", "f6"),
+            ("c7", "d7"),
+            ("e8", "f7"),
+            ("d7", "b7"),
+            ("d8", "d3"),
+            ("b7", "b8"),
+            ("d3", "h7"),
+            ("b8", "c8"),
+            ("f7", "g6"),
+            ("c8", "e6"),
+        ])
+        .await;
+        handle.info().await.unwrap();
+        assert_eq!((finished("checkmate"), finished("stalemate")), (1, 1));
+    }
+
+    #[test]
+    fn malformed_frames_are_rejected() {
+        assert!(matches!(
+            parse_frame("not json", "a"),
+            Err(RoomError::MalformedMessage(_))
+        ));
+        assert!(matches!(
+            parse_frame(r#"{"sender_id":"a"}"#, "a"),
+            Err(RoomError::MalformedMessage(_))
+        ));
+        assert!(matches!(
+            parse_frame(r#"{"sender_id":1,"data":"{}"}"#, "a"),
+            Err(RoomError::MalformedMessage(_))
+        ));
+        assert_eq!(
+            parse_frame(r#"{"sender_id":"b","data":"{}"}"#, "a").err(),
+            Some(RoomError::SenderMismatch {
+                sender_id: "b".to_string()
+            })
+        );
+        assert_eq!(parse_frame(r#"{"sender_id":"a","data":"{}"}"#, "a").unwrap().data, "{}");
+    }
+
+    #[tokio::test]
+    async fn malformed_and_illegal_messages_are_not_relayed() {
+        let registry = Registry::default();
+        let (handle, mut a) = seat(&registry, "a").await;
+        let (_, mut b) = seat(&registry, "b").await;
+
+        handle.relay("a", frame("a", json!("not an object"))).await.unwrap();
+        handle.relay("a", move_frame("a", "e2", "e5")).await.unwrap();
+        handle.relay("a", move_frame("a", "e2", "e4")).await.unwrap();
+        handle.relay("a", move_frame("a", "d2", "d4")).await.unwrap();
+
+        for expected in ["Malformed message", "Illegal move", "Not your turn"] {
+            let Message::Text(text) = a.recv().await.unwrap() else {
+                panic!("expected text");
+            };
+            assert!(text.contains(expected), "{} should contain {}", text, expected);
+        }
+        let Message::Text(text) = b.recv().await.unwrap() else {
+            panic!("expected text");
+        };
+        assert_eq!(text, move_frame("a", "e2", "e4"));
+        assert!(b.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn idle_clocked_game_is_adjudicated_on_expiry() {
+        let registry = Registry::default();
+        let time_control = Some(TimeControl::new(60, 0).unwrap());
+        let (handle, mut a) = registry
+            .join("room", "a", "standard", time_control, false)
+            .await
+            .unwrap();
+        let (_, _b) = registry.join("room", "b", "standard", None, false).await.unwrap();
+        handle.relay("a", move_frame("a", "e2", "e4")).await.unwrap();
+
+        let patient = RoomTtls {
+            waiting: Duration::from_secs(60),
+            idle_game: Duration::from_secs(60),
+        };
+        assert_eq!(handle.expire(patient).await, Ok(false));
+
+        let impatient = RoomTtls {
+            waiting: Duration::ZERO,
+            idle_game: Duration::ZERO,
+        };
+        assert_eq!(handle.expire(impatient).await, Ok(true));
+        assert!(registry.get("room").is_none());
+
+        let mut types = Vec::new();
+        while let Some(Message::Text(text)) = a.recv().await {
+            let ws_message: WsMessage = serde_json::from_str(&text).unwrap();
+            let data: serde_json::Value = serde_json::from_str(&ws_message.data).unwrap();
+            if data["message_type"] == "adjudicated" {
+                assert_eq!(data["loser"], "Black");
+            }
+            types.push(data["message_type"].as_str().unwrap().to_string());
+        }
+        assert!(types.ends_with(&["adjudicated".to_string(), "expired".to_string()]));
+    }
+
+    #[tokio::test]
+    async fn private_room_checks_password_and_invite() {
+        let registry = Registry::default();
+        let access = RoomAccess::new(Some("hunter2"), Some("b".to_string()));
+        let room = registry.create(RoomSettings::implicit("standard", None), access);
+        assert_eq!(room.len(), 32);
+
+        assert_eq!(
+            registry.join(&room, "a", "standard", None, false).await.err(),
+            Some(RoomError::WrongPassword)
+        );
+        assert!(registry.join(&room, "a", "standard", None, true).await.is_ok());
+        assert_eq!(
+            registry.join(&room, "c", "standard", None, true).await.err(),
+            Some(RoomError::NotInvited)
+        );
+        assert!(registry.join(&room, "b", "standard", None, true).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn created_room_sends_settings_and_assigns_colours() {
+        let registry = Registry::default();
+        let settings = RoomSettings {
+            colour: Some(ColourPreference::Black),
+            ..RoomSettings::implicit("shuffled", None)
+        };
+        let room = registry.create(settings, RoomAccess::default());
+
+        assert_eq!(
+            registry.join_existing("missing", "a", false).await.err(),
+            Some(RoomError::RoomNotFound)
+        );
+        let (_, mut a) = registry.join_existing(&room, "a", false).await.unwrap();
+        let (_, mut b) = registry.join_existing(&room, "b", false).await.unwrap();
+
+        let data = |message: Option<Message>| {
+            let Some(Message::Text(text)) = message else {
+                panic!("expected text");
+            };
+            let ws_message: WsMessage = serde_json::from_str(&text).unwrap();
+            serde_json::from_str::<serde_json::Value>(&ws_message.data).unwrap()
+        };
+        let settings = data(a.recv().await);
+        assert_eq!(settings["message_type"], "settings");
+        assert_eq!(settings["settings"]["rule_set"], "shuffled");
+        assert_eq!(data(b.recv().await)["message_type"], "settings");
+        assert_eq!(data(a.recv().await)["colour"], "Black");
+        assert_eq!(data(b.recv().await)["colour"], "White");
+    }
+
+    #[tokio::test]
+    async fn correspondence_game_keeps_seats_and_takes_posted_moves() {
+        let registry = Registry::default();
+        let settings = RoomSettings {
+            colour: Some(ColourPreference::White),
+            correspondence: Some(Correspondence::new(3).unwrap()),
+            ..RoomSettings::implicit("standard", None)
+        };
+        let room = registry.create(settings, RoomAccess::default());
+        let e2e4 = MoveData {
+            start_sq_coords: "e2".to_string(),
+            end_sq_coords: "e4".to_string(),
+            last_pawn_action: String::new(),
+        };
+
+        // each player is handed the token for their seat once, on taking it
+        let seat_token = |outbox: &mut mpsc::Receiver<Message>| {
+            let mut tokens = Vec::new();
+            while let Ok(Message::Text(text)) = outbox.try_recv() {
+                let ws_message: WsMessage = serde_json::from_str(&text).unwrap();
+                let data: serde_json::Value = serde_json::from_str(&ws_message.data).unwrap();
+                if data["message_type"] == "seat" {
+                    tokens.push(data["seat_token"].as_str().unwrap().to_string());
+                }
+            }
+            tokens
+        };
+
+        let (handle, mut a) = registry.join_existing(&room, "a", false).await.unwrap();
+        assert_eq!(handle.leave("a").await, Ok(Departure::Parked));
+        let a_token = seat_token(&mut a).pop().expect("a should get a seat token");
+        let (_, mut b) = registry.join_existing(&room, "b", false).await.unwrap();
+        let b_token = seat_token(&mut b).pop().expect("b should get a seat token");
+        assert_eq!(
+            registry.join_existing(&room, "c", false).await.err(),
+            Some(RoomError::SeatReserved)
+        );
+        assert_eq!(handle.leave("b").await, Ok(Departure::Parked));
+        assert!(registry.get(&room).is_some());
+
+        let info = handle.info().await.unwrap();
+        assert_eq!(info.white, Some("a".to_string()));
+        assert!(info.move_deadline.is_some());
+        assert_eq!(
+            handle.play_move("c", &a_token, false, e2e4.clone()).await,
+            Err(RoomError::PlayerNotInRoom)
+        );
+        // b's id is known to everyone in the room, but their token isn't
+        assert_eq!(
+            handle.play_move("a", &b_token, false, e2e4.clone()).await,
+            Err(RoomError::WrongSeatToken)
+        );
+        handle.play_move("a", &a_token, false, e2e4).await.unwrap();
+
+        let (_, mut b) = registry.join_existing(&room, "b", false).await.unwrap();
+        let mut position = None;
+        while let Ok(Message::Text(text)) = b.try_recv() {
+            let ws_message: WsMessage = serde_json::from_str(&text).unwrap();
+            let data: serde_json::Value = serde_json::from_str(&ws_message.data).unwrap();
+            assert_ne!(data["message_type"], "seat", "returning players keep their token");
+            if data["message_type"] == "position" {
+                position = Some(data);
+            }
+        }
+        let position = position.expect("returning players get the position");
+        let mut game = Game::init("standard".to_string()).unwrap();
+        game.from_dump(position["game"].as_str().unwrap().to_string()).unwrap();
+        assert_eq!(game.move_num(), 1);
+
+        let mut snapshot = handle.shutdown().await.unwrap();
+        snapshot.move_deadline = Some(Utc::now() - chrono::Duration::hours(1));
+        let registry = Registry::default();
+        registry.restore(snapshot);
+        let handle = registry.get(&room).unwrap();
+        let ttls = RoomTtls {
+            waiting: Duration::from_secs(60),
+            idle_game: Duration::from_secs(60),
+        };
+        assert_eq!(handle.expire(ttls).await, Ok(true));
+        assert!(registry.get(&room).is_none());
+    }
+}
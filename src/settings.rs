@@ -0,0 +1,71 @@
+use crate::clock::{Correspondence, TimeControl};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Which colour the room's creator, its first player, gets.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColourPreference {
+    White,
+    Black,
+    #[default]
+    Random,
+}
+
+impl ColourPreference {
+    /// Settles a random preference, so every joiner is told the same colours.
+    pub fn resolve(self) -> ColourPreference {
+        match self {
+            ColourPreference::Random if rand::thread_rng().gen() => ColourPreference::White,
+            ColourPreference::Random => ColourPreference::Black,
+            preference => preference,
+        }
+    }
+}
+
+/// Public rooms are listed in the lobby; private ones can only be joined by id.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    Public,
+    #[default]
+    Private,
+}
+
+/// A room's game settings, declared once when the room is created and sent to every joiner.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoomSettings {
+    pub rule_set: String,
+    #[serde(default)]
+    pub time_control: Option<TimeControl>,
+    /// Set for correspondence games, which keep their seats while the players are away.
+    #[serde(default)]
+    pub correspondence: Option<Correspondence>,
+    #[serde(default)]
+    pub rated: bool,
+    /// `None` for rooms opened straight from a websocket URL, where the clients negotiate colours
+    /// between themselves.
+    #[serde(default)]
+    pub colour: Option<ColourPreference>,
+    #[serde(default)]
+    pub visibility: Visibility,
+    /// Options for the rule set's variant, passed through to the clients untouched.
+    #[serde(default)]
+    pub variant: Map<String, Value>,
+}
+
+impl RoomSettings {
+    /// Settings for a room started by the first websocket to name it.
+    pub fn implicit(rule_set: &str, time_control: Option<TimeControl>) -> RoomSettings {
+        RoomSettings {
+            rule_set: rule_set.to_string(),
+            time_control,
+            correspondence: None,
+            rated: false,
+            colour: None,
+            visibility: Visibility::Private,
+            variant: Map::new(),
+        }
+    }
+}
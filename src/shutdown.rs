@@ -0,0 +1,53 @@
+use crate::State;
+use futures::future::join_all;
+use std::sync::atomic::Ordering;
+use tokio::signal;
+
+/// Resolves on Ctrl+C or, on unix, SIGTERM (what the platform sends before a redeploy).
+pub async fn signal() {
+    let ctrl_c = async {
+        if let Err(err) = signal::ctrl_c().await {
+            tracing::error!(%err, "failed to listen for ctrl+c");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match signal::unix::signal(signal::unix::SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(err) => {
+                tracing::error!(%err, "failed to listen for SIGTERM");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+/// Stops taking new connections, tells every player the server is restarting, and saves each
+/// room so it can be restored on the next start.
+pub(crate) async fn drain(state: &State) {
+    tracing::info!("shutting down: draining rooms");
+    state.shutting_down.store(true, Ordering::SeqCst);
+
+    let handles = state.registry.handles();
+    let snapshots: Vec<_> = join_all(handles.iter().map(|handle| handle.shutdown()))
+        .await
+        .into_iter()
+        .filter_map(Result::ok)
+        .collect();
+
+    match state.snapshots.save(&snapshots).await {
+        Ok(()) => tracing::info!(rooms = snapshots.len(), "saved rooms"),
+        Err(err) => tracing::error!(%err, "failed to save rooms"),
+    }
+}
@@ -0,0 +1,154 @@
+use crate::room::RoomSnapshot;
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+use tokio::{fs, sync::mpsc};
+
+/// Rooms saved during a graceful shutdown, kept as a single JSON file.
+#[derive(Debug, Clone)]
+pub struct SnapshotStore {
+    path: PathBuf,
+}
+
+impl SnapshotStore {
+    pub fn new(path: impl AsRef<Path>) -> SnapshotStore {
+        SnapshotStore {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Writes to a temporary file first so a crash mid-write never leaves a truncated snapshot.
+    pub async fn save(&self, rooms: &[RoomSnapshot]) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).await?;
+        }
+        let json = serde_json::to_vec(rooms).map_err(io::Error::other)?;
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, json).await?;
+        fs::rename(&tmp_path, &self.path).await
+    }
+
+    /// Checks that a snapshot could be saved right now by writing and removing a probe file.
+    pub async fn check(&self) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).await?;
+        }
+        let probe_path = self.path.with_extension("probe");
+        fs::write(&probe_path, b"").await?;
+        fs::remove_file(&probe_path).await
+    }
+
+    /// Reads and removes the saved rooms, so a snapshot is restored at most once.
+    pub async fn take(&self) -> io::Result<Vec<RoomSnapshot>> {
+        let json = match fs::read(&self.path).await {
+            Ok(json) => json,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+        fs::remove_file(&self.path).await?;
+        serde_json::from_slice(&json).map_err(io::Error::other)
+    }
+}
+
+/// Correspondence games, one JSON file per room, rewritten after every move so they outlive a
+/// crash as well as a graceful restart.
+#[derive(Debug, Clone)]
+pub struct GameStore {
+    dir: PathBuf,
+}
+
+enum GameWrite {
+    Save(Box<RoomSnapshot>),
+    Remove(String),
+}
+
+/// Queues writes for a `GameStore`. A single task applies them in order, so rooms never wait on
+/// the disk and a later save can't be overtaken by an earlier one.
+#[derive(Debug, Clone)]
+pub struct GameWriter {
+    tx: mpsc::UnboundedSender<GameWrite>,
+}
+
+impl GameStore {
+    pub fn new(dir: impl AsRef<Path>) -> GameStore {
+        GameStore {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    fn path(&self, room: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", room))
+    }
+
+    async fn save(&self, snapshot: &RoomSnapshot) -> io::Result<()> {
+        fs::create_dir_all(&self.dir).await?;
+        let json = serde_json::to_vec(snapshot).map_err(io::Error::other)?;
+        let path = self.path(&snapshot.room);
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, json).await?;
+        fs::rename(&tmp_path, &path).await
+    }
+
+    async fn remove(&self, room: &str) -> io::Result<()> {
+        match fs::remove_file(self.path(room)).await {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    /// Reads every saved game. Unreadable files are logged and skipped rather than failing the
+    /// whole load.
+    pub async fn load(&self) -> io::Result<Vec<RoomSnapshot>> {
+        let mut entries = match fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err),
+        };
+
+        let mut games = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().is_none_or(|extension| extension != "json") {
+                continue;
+            }
+            let game = fs::read(&path)
+                .await
+                .and_then(|json| serde_json::from_slice(&json).map_err(io::Error::other));
+            match game {
+                Ok(game) => games.push(game),
+                Err(err) => tracing::error!(path = %path.display(), %err, "skipping saved game"),
+            }
+        }
+        Ok(games)
+    }
+
+    pub fn spawn_writer(self) -> GameWriter {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Some(write) = rx.recv().await {
+                let (room, result) = match write {
+                    GameWrite::Save(snapshot) => (snapshot.room.clone(), self.save(&snapshot).await),
+                    GameWrite::Remove(room) => {
+                        let result = self.remove(&room).await;
+                        (room, result)
+                    }
+                };
+                if let Err(err) = result {
+                    tracing::error!(%room, %err, "failed to write game");
+                }
+            }
+        });
+        GameWriter { tx }
+    }
+}
+
+impl GameWriter {
+    pub fn save(&self, snapshot: RoomSnapshot) {
+        let _ = self.tx.send(GameWrite::Save(Box::new(snapshot)));
+    }
+
+    pub fn remove(&self, room: &str) {
+        let _ = self.tx.send(GameWrite::Remove(room.to_string()));
+    }
+}
@@ -0,0 +1,24 @@
+use std::env;
+use tracing_subscriber::{fmt, EnvFilter};
+
+const LOG_LEVEL: &str = "info";
+
+/// Installs the global subscriber. The filter comes from `RUST_LOG`, falling back to `LOG_LEVEL`
+/// (default `info`); `LOG_FORMAT` picks `json` for log aggregation, `compact`, or the default
+/// human-readable output.
+pub fn init() {
+    let filter = EnvFilter::try_from_default_env()
+        .or_else(|_| EnvFilter::try_new(env::var("LOG_LEVEL").unwrap_or_else(|_| LOG_LEVEL.to_string())))
+        .unwrap_or_else(|_| EnvFilter::new(LOG_LEVEL));
+    let builder = fmt().with_env_filter(filter);
+
+    let result = match env::var("LOG_FORMAT").as_deref() {
+        Ok("json") => builder.json().with_current_span(true).with_span_list(true).try_init(),
+        Ok("compact") => builder.compact().try_init(),
+        _ => builder.try_init(),
+    };
+
+    if let Err(err) = result {
+        eprintln!("failed to install tracing subscriber: {}", err);
+    }
+}
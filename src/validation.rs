@@ -0,0 +1,185 @@
+use crate::{
+    bot::BOT_ID,
+    clock::{Correspondence, TimeControl},
+};
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chess::Difficulty;
+use serde_json::{json, Map, Value};
+
+/// Rule sets the chess crate knows how to build a game for.
+pub const RULE_SETS: [&str; 2] = ["standard", "shuffled"];
+
+const MAX_PLAYER_ID_LEN: usize = 32;
+const MAX_ROOM_ID_LEN: usize = 64;
+const MAX_PASSWORD_LEN: usize = 128;
+const MAX_VARIANT_BYTES: usize = 1024;
+
+#[derive(Debug, PartialEq)]
+pub enum JoinRejection {
+    InvalidRoomId,
+    InvalidPlayerId,
+    ReservedPlayerId,
+    UnknownRuleSet(String),
+    RuleSetMismatch { room_rule_set: String },
+    DuplicatePlayerId,
+    InvalidTimeControl(String),
+    ShuttingDown,
+    OriginNotAllowed,
+    InvalidPassword,
+    WrongPassword,
+    InvalidVariant,
+    RoomNotFound,
+    InvalidBotLevel(u8),
+}
+
+impl IntoResponse for JoinRejection {
+    fn into_response(self) -> Response {
+        let (status, error) = match self {
+            JoinRejection::InvalidRoomId => (
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "Room id must be 1-{} characters of a-z, A-Z, 0-9, - or _",
+                    MAX_ROOM_ID_LEN
+                ),
+            ),
+            JoinRejection::InvalidPlayerId => (
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "Player id must be 1-{} characters of a-z, A-Z, 0-9, - or _",
+                    MAX_PLAYER_ID_LEN
+                ),
+            ),
+            JoinRejection::ReservedPlayerId => (
+                StatusCode::BAD_REQUEST,
+                format!("Player id {} is reserved for the server's bot", BOT_ID),
+            ),
+            JoinRejection::UnknownRuleSet(rule_set) => {
+                (StatusCode::BAD_REQUEST, format!("Unknown rule set: {}", rule_set))
+            }
+            JoinRejection::RuleSetMismatch { room_rule_set } => (
+                StatusCode::CONFLICT,
+                format!("Room is playing rule set: {}", room_rule_set),
+            ),
+            JoinRejection::DuplicatePlayerId => (StatusCode::CONFLICT, "Player id already in room".to_string()),
+            JoinRejection::InvalidTimeControl(reason) => (StatusCode::BAD_REQUEST, reason),
+            JoinRejection::ShuttingDown => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Server is restarting, try again shortly".to_string(),
+            ),
+            JoinRejection::OriginNotAllowed => (StatusCode::FORBIDDEN, "Origin not allowed".to_string()),
+            JoinRejection::InvalidPassword => (
+                StatusCode::BAD_REQUEST,
+                format!("Password must be 1-{} characters", MAX_PASSWORD_LEN),
+            ),
+            JoinRejection::WrongPassword => (StatusCode::FORBIDDEN, "Wrong room password".to_string()),
+            JoinRejection::InvalidVariant => (
+                StatusCode::BAD_REQUEST,
+                format!("Variant options must be at most {} bytes of JSON", MAX_VARIANT_BYTES),
+            ),
+            JoinRejection::RoomNotFound => (StatusCode::NOT_FOUND, "Room does not exist".to_string()),
+            JoinRejection::InvalidBotLevel(level) => (
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "Bot levels run from {} to {}, not {}",
+                    Difficulty::LEVELS.start(),
+                    Difficulty::LEVELS.end(),
+                    level
+                ),
+            ),
+        };
+
+        (status, Json(json!({ "error": error }))).into_response()
+    }
+}
+
+fn is_valid_id(id: &str, max_len: usize) -> bool {
+    !id.is_empty() && id.len() <= max_len && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+pub fn validate_room_id(room: &str) -> Result<(), JoinRejection> {
+    if !is_valid_id(room, MAX_ROOM_ID_LEN) {
+        return Err(JoinRejection::InvalidRoomId);
+    }
+    Ok(())
+}
+
+pub fn validate_player_id(id: &str) -> Result<(), JoinRejection> {
+    if !is_valid_id(id, MAX_PLAYER_ID_LEN) {
+        return Err(JoinRejection::InvalidPlayerId);
+    }
+    if id == BOT_ID {
+        return Err(JoinRejection::ReservedPlayerId);
+    }
+    Ok(())
+}
+
+pub fn validate_bot_level(level: u8) -> Result<(), JoinRejection> {
+    if !Difficulty::LEVELS.contains(&level) {
+        return Err(JoinRejection::InvalidBotLevel(level));
+    }
+    Ok(())
+}
+
+pub fn validate_rule_set(rule_set: &str) -> Result<(), JoinRejection> {
+    if !RULE_SETS.contains(&rule_set) {
+        return Err(JoinRejection::UnknownRuleSet(rule_set.to_string()));
+    }
+    Ok(())
+}
+
+pub fn validate_password(password: &str) -> Result<(), JoinRejection> {
+    if password.is_empty() || password.chars().count() > MAX_PASSWORD_LEN {
+        return Err(JoinRejection::InvalidPassword);
+    }
+    Ok(())
+}
+
+pub fn validate_variant(variant: &Map<String, Value>) -> Result<(), JoinRejection> {
+    if serde_json::to_string(variant).map_or(true, |json| json.len() > MAX_VARIANT_BYTES) {
+        return Err(JoinRejection::InvalidVariant);
+    }
+    Ok(())
+}
+
+pub fn validate_time_control(
+    initial_secs: Option<u64>,
+    increment_secs: Option<u64>,
+) -> Result<Option<TimeControl>, JoinRejection> {
+    match (initial_secs, increment_secs) {
+        (None, None) => Ok(None),
+        (initial_secs, increment_secs) => TimeControl::new(initial_secs.unwrap_or(0), increment_secs.unwrap_or(0))
+            .map(Some)
+            .map_err(JoinRejection::InvalidTimeControl),
+    }
+}
+
+/// Correspondence games run on move deadlines instead of a clock, so they can't have both.
+pub fn validate_correspondence(
+    days_per_move: Option<u64>,
+    time_control: Option<TimeControl>,
+) -> Result<Option<Correspondence>, JoinRejection> {
+    match (days_per_move, time_control) {
+        (None, _) => Ok(None),
+        (Some(_), Some(_)) => Err(JoinRejection::InvalidTimeControl(
+            "Correspondence games can't also have a clock".to_string(),
+        )),
+        (Some(days_per_move), None) => Correspondence::new(days_per_move)
+            .map(Some)
+            .map_err(JoinRejection::InvalidTimeControl),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn players_cannot_take_the_bot_id() {
+        assert_eq!(validate_player_id(BOT_ID), Err(JoinRejection::ReservedPlayerId));
+        assert_eq!(validate_player_id("bot2"), Ok(()));
+    }
+}